@@ -1,26 +1,223 @@
-use serde::Deserialize;
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::path::Path;
 
 #[allow(unused)]
 const CONFIG_FILE_PATH: &str = "data/config.toml";
 
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoFormat {
+    Any,
+    Numeric,
+    Alphanumeric,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DisperseMode {
+    Fixed,
+    Range,
+    Required,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeePayerRotation {
+    RoundRobin,
+    LeastUsed,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyAssignment {
+    Sticky,
+    Rotating,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountOrdering {
+    Random,
+    File,
+    Allocation,
+    Priority,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// Share of forwarded GRASS routed away from the CEX address, which receives the remainder.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SplitRule {
+    #[serde(alias = "DESTINATION")]
+    pub destination: String,
+    #[serde(alias = "PERCENT", default)]
+    pub percent: f64,
+    #[serde(alias = "MIN_AMOUNT", default)]
+    pub min_amount: f64,
+}
+
+/// Fee payer keys, given as a single string by configs written before payer rotation.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FeePayerKeys {
+    Single(String),
+    Many(Vec<String>),
+}
+
+fn deserialize_fee_payer_pks<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pks = match FeePayerKeys::deserialize(deserializer)? {
+        FeePayerKeys::Single(pk) if pk.is_empty() => vec![],
+        FeePayerKeys::Single(pk) => vec![pk],
+        FeePayerKeys::Many(pks) => pks,
+    };
+
+    Ok(pks)
+}
+
+/// Rejects shares that are not a percentage, add up to more than 100% or have a negative minimum.
+pub fn validate_split_rules(split_rules: &[SplitRule]) -> eyre::Result<()> {
+    for rule in split_rules {
+        if !rule.percent.is_finite() || !(0.0..=100.0).contains(&rule.percent) {
+            eyre::bail!(
+                "Split rule for `{}` must take 0 to 100 percent, got {}",
+                rule.destination,
+                rule.percent
+            );
+        }
+
+        if !rule.min_amount.is_finite() || rule.min_amount < 0.0 {
+            eyre::bail!(
+                "Split rule for `{}` must have a non-negative minimum amount, got {}",
+                rule.destination,
+                rule.min_amount
+            );
+        }
+    }
+
+    let total_bps: u64 = split_rules
+        .iter()
+        .map(|rule| (rule.percent * 100.0).round() as u64)
+        .sum();
+
+    if total_bps > 10_000 {
+        eyre::bail!("Split rules add up to more than 100%");
+    }
+
+    Ok(())
+}
+
+/// Browser-like headers sent with every Grass API request of the wallet it's assigned to.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HeaderProfile {
+    #[serde(alias = "USER_AGENT")]
+    pub user_agent: String,
+    #[serde(alias = "ORIGIN", default)]
+    pub origin: String,
+    #[serde(alias = "REFERER", default)]
+    pub referer: String,
+    #[serde(alias = "ACCEPT_LANGUAGE", default)]
+    pub accept_language: String,
+}
+
+/// Rejects header profiles with values that can't be sent as HTTP headers, so a typo fails at
+/// startup instead of on every Grass API request of the wallets using the profile.
+pub fn validate_header_profiles(header_profiles: &[HeaderProfile]) -> eyre::Result<()> {
+    for (index, profile) in header_profiles.iter().enumerate() {
+        for (name, value) in [
+            ("USER_AGENT", &profile.user_agent),
+            ("ORIGIN", &profile.origin),
+            ("REFERER", &profile.referer),
+            ("ACCEPT_LANGUAGE", &profile.accept_language),
+        ] {
+            if value.is_empty() {
+                continue;
+            }
+
+            HeaderValue::from_str(value).map_err(|e| {
+                eyre::eyre!(
+                    "Header profile #{}: invalid {name} `{value}`: {e}",
+                    index + 1
+                )
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub struct Config {
     pub solana_rpc_url: String,
+    pub rpc_proxy_reads: bool,
+    pub rpc_proxy_sends: bool,
+    pub account_cache_ttl_secs: u64,
+    pub claim_version: u32,
     pub withdraw_to_cex: bool,
+    pub cex_memo_required: bool,
+    pub cex_memo_format: MemoFormat,
     pub mobile_proxies: bool,
+    pub proxy_assignment: ProxyAssignment,
+    pub proxy_check_url: String,
+    pub proxy_max_latency_ms: u64,
+    pub proxy_max_failures: u32,
     pub swap_ip_link: String,
+    pub swap_ip_attempts: u32,
+    pub swap_ip_timeout_secs: u64,
+    pub swap_ip_poll_interval_secs: u64,
     pub claim_sleep_range: [u64; 2],
+    pub account_ordering: AccountOrdering,
+    pub account_ordering_seed: u64,
+    pub http_timeout_secs: u64,
+    pub http_connect_timeout_secs: u64,
+    pub http2_prior_knowledge: bool,
+    pub http_min_tls_version: TlsVersion,
+    pub http_retry_attempts: u32,
+    pub http_retry_base_delay_ms: u64,
+    pub http_retry_max_delay_ms: u64,
+    pub http_proxy_rps: f64,
+    pub http_host_rps: f64,
+    pub offline_claim: bool,
+    pub proofs_file_path: String,
     pub use_external_fee_pay: bool,
-    pub external_fee_payer_pk: String,
+    #[serde(
+        alias = "EXTERNAL_FEE_PAYER_PK",
+        deserialize_with = "deserialize_fee_payer_pks"
+    )]
+    pub external_fee_payer_pks: Vec<String>,
+    pub fee_payer_min_balance: f64,
+    pub fee_payer_rotation: FeePayerRotation,
     pub collector_pubkey: String,
+    pub funder_pk: String,
+    pub disperse_mode: DisperseMode,
+    pub disperse_amount: f64,
+    pub disperse_amount_range: [f64; 2],
+    pub disperse_batch_size: usize,
+    #[serde(default)]
+    pub split_rules: Vec<SplitRule>,
+    #[serde(default)]
+    pub header_profiles: Vec<HeaderProfile>,
 }
 
 impl Config {
     async fn read_from_file(path: impl AsRef<Path>) -> eyre::Result<Self> {
         let cfg_str = tokio::fs::read_to_string(path).await?;
-        Ok(toml::from_str(&cfg_str)?)
+        let config = toml::from_str::<Self>(&cfg_str)?;
+
+        validate_split_rules(&config.split_rules)?;
+        validate_header_profiles(&config.header_profiles)?;
+
+        Ok(config)
     }
 
     pub async fn read_default() -> Self {
@@ -29,3 +226,41 @@ impl Config {
             .expect("Default config to be valid")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(user_agent: &str, referer: &str) -> HeaderProfile {
+        HeaderProfile {
+            user_agent: user_agent.to_string(),
+            origin: String::new(),
+            referer: referer.to_string(),
+            accept_language: "en-US,en;q=0.9".to_string(),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_header_profiles() {
+        let profiles = [
+            profile(
+                "Mozilla/5.0 (X11; Linux x86_64)",
+                "https://grassfoundation.io/",
+            ),
+            profile("Mozilla/5.0 (Macintosh)", ""),
+        ];
+
+        assert!(validate_header_profiles(&profiles).is_ok());
+    }
+
+    #[test]
+    fn rejects_header_values_with_control_characters() {
+        for profiles in [
+            [profile("Mozilla/5.0\nX-Injected: 1", "")],
+            [profile("Mozilla/5.0", "https://grassfoundation.io/\r")],
+            [profile("Mozilla/5.0\u{7f}", "")],
+        ] {
+            assert!(validate_header_profiles(&profiles).is_err());
+        }
+    }
+}
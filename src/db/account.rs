@@ -1,39 +1,103 @@
-use std::str::FromStr;
+use std::{collections::BTreeMap, str::FromStr};
 
-use reqwest::Proxy;
 use serde::{Deserialize, Serialize};
 use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
 
+use crate::{
+    config::{HeaderProfile, SplitRule},
+    utils::proxy::ProxyEntry,
+};
+
+/// Distributor version the single `claimed` flag of older databases stands for.
+const LEGACY_CLAIM_VERSION: u32 = 1;
+
+/// Claim state of a single merkle distributor version.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ClaimRecord {
+    pub allocation: u64,
+    #[serde(default)]
+    pub sent_allocation: u64,
+    pub claimed: bool,
+    pub signature: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Account {
     private_key: String,
     proxy: Option<String>,
+    #[serde(default)]
+    swap_link: Option<String>,
     address: String,
     cex_address: String,
-    allocation: f64,
-    claimed: bool,
+    #[serde(default)]
+    exit_ip: Option<String>,
+    #[serde(default)]
+    header_profile: Option<HeaderProfile>,
+    #[serde(default)]
+    cex_memo: Option<String>,
+    #[serde(default)]
+    priority: u32,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    split_rules: Option<Vec<SplitRule>>,
+    #[serde(default)]
+    claims: BTreeMap<u32, ClaimRecord>,
+    #[serde(default, rename = "claimed", skip_serializing)]
+    legacy_claimed: Option<bool>,
+    #[serde(default, rename = "allocation", skip_serializing)]
+    legacy_allocation: Option<f64>,
+    #[serde(default)]
+    forwarded_grass: bool,
     closed_ata: bool,
     collected_sol: bool,
+    #[serde(default)]
+    funded: bool,
+    #[serde(default)]
+    funded_lamports: u64,
+    #[serde(default)]
+    funding_signature: Option<String>,
 }
 
 impl Account {
-    pub fn new(private_key: &str, proxy: Option<String>, cex_address: &str) -> Self {
+    pub fn new(
+        private_key: &str,
+        proxy: Option<ProxyEntry>,
+        cex_address: &str,
+        cex_memo: Option<String>,
+    ) -> Self {
         let signer = Keypair::from_base58_string(private_key);
         let address = signer.pubkey();
 
         Self {
             private_key: private_key.to_string(),
-            proxy,
+            proxy: proxy.as_ref().map(|proxy| proxy.url.clone()),
+            swap_link: proxy.and_then(|proxy| proxy.swap_link),
             address: address.to_string(),
             cex_address: cex_address.to_string(),
+            cex_memo,
             ..Default::default()
         }
     }
 
-    pub fn proxy(&self) -> Option<Proxy> {
-        self.proxy
-            .as_ref()
-            .map(|proxy| Proxy::all(proxy).expect("Proxy to be valid"))
+    pub fn get_proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    pub fn get_swap_link(&self) -> Option<&str> {
+        self.swap_link.as_deref()
+    }
+
+    pub fn set_exit_ip(&mut self, exit_ip: String) {
+        self.exit_ip = Some(exit_ip)
+    }
+
+    pub fn get_header_profile(&self) -> Option<&HeaderProfile> {
+        self.header_profile.as_ref()
+    }
+
+    pub fn set_header_profile(&mut self, header_profile: HeaderProfile) {
+        self.header_profile = Some(header_profile)
     }
 
     pub fn keypair(&self) -> Keypair {
@@ -44,22 +108,100 @@ impl Account {
         Pubkey::from_str(&self.address).expect("Address to be valid")
     }
 
-    pub fn set_allocation(&mut self, allocation: f64) {
-        self.allocation = allocation
+    /// Moves the claim state of databases written before per-version tracking into the record of
+    /// the version they were claimed with.
+    pub fn migrate_legacy_claim(&mut self) {
+        let (claimed, allocation) = (self.legacy_claimed.take(), self.legacy_allocation.take());
+
+        if claimed.is_none() && allocation.is_none() {
+            return;
+        }
+
+        let claim = self.claims.entry(LEGACY_CLAIM_VERSION).or_default();
+
+        if let Some(allocation) = allocation {
+            claim.allocation = (allocation * 10f64.powi(9)).round() as u64;
+        }
+
+        if claimed == Some(true) {
+            claim.claimed = true;
+            claim.sent_allocation = claim.allocation;
+        }
+    }
+
+    pub fn get_claim_versions(&self) -> impl Iterator<Item = u32> + '_ {
+        self.claims.keys().copied()
+    }
+
+    pub fn get_claim(&self, version: u32) -> Option<&ClaimRecord> {
+        self.claims.get(&version)
+    }
+
+    pub fn set_allocation(&mut self, version: u32, allocation: u64) {
+        self.claims.entry(version).or_default().allocation = allocation
+    }
+
+    pub fn set_sent_allocation(&mut self, version: u32, sent_allocation: u64) {
+        self.claims.entry(version).or_default().sent_allocation = sent_allocation
+    }
+
+    pub fn set_claim_signature(&mut self, version: u32, signature: String) {
+        self.claims.entry(version).or_default().signature = Some(signature)
+    }
+
+    pub fn set_claimed(&mut self, version: u32, signature: Option<String>) {
+        let claim = self.claims.entry(version).or_default();
+        claim.claimed = true;
+
+        if signature.is_some() {
+            claim.signature = signature;
+        }
     }
 
-    pub fn set_claimed(&mut self, claimed: bool) {
-        self.claimed = claimed
+    pub fn reset_claimed(&mut self, version: u32) {
+        self.claims.entry(version).or_default().claimed = false
     }
 
-    pub fn get_claimed(&self) -> bool {
-        self.claimed
+    pub fn get_claimed(&self, version: u32) -> bool {
+        self.claims.get(&version).is_some_and(|claim| claim.claimed)
+    }
+
+    pub fn get_priority(&self) -> u32 {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: u32) {
+        self.priority = priority
+    }
+
+    pub fn get_tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = tags
     }
 
     pub fn get_cex_address(&self) -> &str {
         &self.cex_address
     }
 
+    pub fn get_cex_memo(&self) -> Option<&str> {
+        self.cex_memo.as_deref()
+    }
+
+    pub fn get_split_rules(&self) -> Option<&[SplitRule]> {
+        self.split_rules.as_deref()
+    }
+
+    pub fn get_forwarded_grass(&self) -> bool {
+        self.forwarded_grass
+    }
+
+    pub fn set_forwarded_grass(&mut self, forwarded: bool) {
+        self.forwarded_grass = forwarded
+    }
+
     pub fn get_closed_ata(&self) -> bool {
         self.closed_ata
     }
@@ -75,4 +217,73 @@ impl Account {
     pub fn set_collected_sol(&mut self, collected_sol: bool) {
         self.collected_sol = collected_sol
     }
+
+    pub fn get_funded(&self) -> bool {
+        self.funded
+    }
+
+    pub fn set_funded(&mut self, lamports: u64, signature: Option<String>) {
+        self.funded = true;
+        self.funded_lamports += lamports;
+
+        if signature.is_some() {
+            self.funding_signature = signature;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_legacy_claim_into_versioned_record() {
+        let json = r#"{
+            "private_key": "key",
+            "proxy": null,
+            "address": "11111111111111111111111111111111",
+            "cex_address": "11111111111111111111111111111111",
+            "allocation": 12.5,
+            "claimed": true,
+            "closed_ata": false,
+            "collected_sol": false
+        }"#;
+
+        let mut account = serde_json::from_str::<Account>(json).unwrap();
+        account.migrate_legacy_claim();
+
+        let claim = account.get_claim(LEGACY_CLAIM_VERSION).unwrap();
+        assert_eq!(claim.allocation, 12_500_000_000);
+        assert!(claim.claimed);
+        assert_eq!(claim.sent_allocation, claim.allocation);
+        assert!(account.get_claimed(LEGACY_CLAIM_VERSION));
+        assert!(account.legacy_claimed.is_none() && account.legacy_allocation.is_none());
+
+        let serialized = serde_json::to_value(&account).unwrap();
+        assert!(serialized.get("claimed").is_none());
+        assert!(serialized.get("allocation").is_none());
+    }
+
+    #[test]
+    fn keeps_unclaimed_legacy_allocation_unclaimed() {
+        let json = r#"{
+            "private_key": "key",
+            "proxy": null,
+            "address": "11111111111111111111111111111111",
+            "cex_address": "11111111111111111111111111111111",
+            "allocation": 0.000000001,
+            "claimed": false,
+            "closed_ata": false,
+            "collected_sol": false
+        }"#;
+
+        let mut account = serde_json::from_str::<Account>(json).unwrap();
+        account.migrate_legacy_claim();
+
+        assert_eq!(
+            account.get_claim(LEGACY_CLAIM_VERSION).unwrap().allocation,
+            1
+        );
+        assert!(!account.get_claimed(LEGACY_CLAIM_VERSION));
+    }
 }
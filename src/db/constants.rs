@@ -1,4 +1,6 @@
 pub const PROXIES_FILE_PATH: &str = "data/proxies.txt";
 pub const PRIVATE_KEYS_FILE_PATH: &str = "data/private_keys.txt";
 pub const CEX_ADDRESSES_FILE_PATH: &str = "data/cex_addresses.txt";
+pub const LABELS_FILE_PATH: &str = "data/labels.txt";
 pub const DB_FILE_PATH: &str = "data/db.json";
+pub const FAILURES_FILE_PATH: &str = "data/failures.json";
@@ -1,23 +1,81 @@
-use std::fs::File;
+use std::{fs::File, str::FromStr};
 
 use itertools::{EitherOrBoth, Itertools};
-use rand::{seq::IteratorRandom, thread_rng};
+use rand::{seq::SliceRandom, thread_rng};
 use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
 
-use crate::utils::files::read_file_lines;
+use crate::{
+    config::HeaderProfile,
+    utils::{
+        files::{read_file_lines, read_numbered_file_lines},
+        proxy::{parse_proxy_entry, ProxyEntry},
+    },
+};
 
 use super::{
     account::Account,
-    constants::{CEX_ADDRESSES_FILE_PATH, DB_FILE_PATH, PRIVATE_KEYS_FILE_PATH, PROXIES_FILE_PATH},
+    constants::{
+        CEX_ADDRESSES_FILE_PATH, DB_FILE_PATH, LABELS_FILE_PATH, PRIVATE_KEYS_FILE_PATH,
+        PROXIES_FILE_PATH,
+    },
 };
 
+/// Line of the labels file: `wallet_address,priority[,tags]`, with tags separated by `;`. An
+/// empty priority stands for 0.
+struct Label {
+    wallet_pubkey: Pubkey,
+    priority: u32,
+    tags: Vec<String>,
+}
+
+fn parse_label(line: &str) -> eyre::Result<Label> {
+    let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+
+    let (wallet_address, priority, tags) = match fields[..] {
+        [wallet_address, priority] => (wallet_address, priority, ""),
+        [wallet_address, priority, tags] => (wallet_address, priority, tags),
+        _ => eyre::bail!("Expected `wallet_address,priority[,tags]`, got `{line}`"),
+    };
+
+    let wallet_pubkey = Pubkey::from_str(wallet_address)
+        .map_err(|e| eyre::eyre!("Invalid wallet address `{wallet_address}`: {e}"))?;
+
+    let priority = match priority {
+        "" => 0,
+        priority => priority
+            .parse::<u32>()
+            .map_err(|_| eyre::eyre!("Invalid priority `{priority}`"))?,
+    };
+
+    let tags = tags
+        .split(';')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    Ok(Label {
+        wallet_pubkey,
+        priority,
+        tags,
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Database(pub Vec<Account>);
 
 impl Database {
     async fn read_from_file(file_path: &str) -> eyre::Result<Self> {
         let contents = tokio::fs::read_to_string(file_path).await?;
-        let db = serde_json::from_str::<Self>(&contents)?;
+        let mut db = serde_json::from_str::<Self>(&contents)?;
+
+        for account in &mut db.0 {
+            account.migrate_legacy_claim();
+        }
+
+        db.apply_labels().await?;
+
         Ok(db)
     }
 
@@ -30,7 +88,7 @@ impl Database {
 
     pub async fn new() -> eyre::Result<Self> {
         let private_keys = read_file_lines(PRIVATE_KEYS_FILE_PATH).await.unwrap();
-        let proxies = read_file_lines(PROXIES_FILE_PATH).await.unwrap();
+        let proxies = Self::read_proxies().await?;
         let cex_addresses = read_file_lines(CEX_ADDRESSES_FILE_PATH).await.unwrap();
 
         if cex_addresses.len() != private_keys.len() {
@@ -60,26 +118,110 @@ impl Database {
                 }
             };
 
-            let account = Account::new(&private_key, proxy, &cex_address);
+            let (cex_address, cex_memo) = match cex_address.split_once(':') {
+                Some((address, memo)) => (address.trim(), Some(memo.trim().to_string())),
+                None => (cex_address.as_str(), None),
+            };
+
+            let account = Account::new(&private_key, proxy, cex_address, cex_memo);
             data.push(account);
         }
 
+        let mut db = Self(data);
+        db.apply_labels().await?;
+
         let db_file = File::create(DB_FILE_PATH)?;
-        serde_json::to_writer_pretty(db_file, &data)?;
+        serde_json::to_writer_pretty(db_file, &db)?;
+
+        Ok(db)
+    }
+
+    /// Sets the priority and tags of every account from the labels file. The file is read on
+    /// every run, so edits take effect without recreating the database; accounts missing from it,
+    /// or all of them without the file, get priority 0 and no tags.
+    async fn apply_labels(&mut self) -> eyre::Result<()> {
+        let lines = read_numbered_file_lines(LABELS_FILE_PATH)
+            .await
+            .unwrap_or_default();
+
+        let mut labels = Vec::with_capacity(lines.len());
+        let mut errors = vec![];
+
+        for (line_number, line) in lines {
+            match parse_label(&line) {
+                Ok(label) => labels.push((line_number, label)),
+                Err(e) => errors.push(format!("{LABELS_FILE_PATH}:{line_number}: {e}")),
+            }
+        }
+
+        if !errors.is_empty() {
+            eyre::bail!("Invalid labels:\n{}", errors.join("\n"));
+        }
 
-        Ok(Self(data))
+        for account in &mut self.0 {
+            account.set_priority(0);
+            account.set_tags(vec![]);
+        }
+
+        for (line_number, label) in labels {
+            match self
+                .0
+                .iter_mut()
+                .find(|a| a.get_pubkey() == label.wallet_pubkey)
+            {
+                Some(account) => {
+                    account.set_priority(label.priority);
+                    account.set_tags(label.tags);
+                }
+                None => tracing::warn!(
+                    "{LABELS_FILE_PATH}:{line_number}: wallet `{}` is not in the database",
+                    label.wallet_pubkey
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses every line of the proxies file, reporting all malformed lines at once.
+    async fn read_proxies() -> eyre::Result<Vec<ProxyEntry>> {
+        let lines = read_numbered_file_lines(PROXIES_FILE_PATH).await?;
+
+        let mut proxies = Vec::with_capacity(lines.len());
+        let mut errors = vec![];
+
+        for (line_number, line) in lines {
+            match parse_proxy_entry(&line) {
+                Ok(proxy) => proxies.push(proxy),
+                Err(e) => errors.push(format!("{PROXIES_FILE_PATH}:{line_number}: {e}")),
+            }
+        }
+
+        if !errors.is_empty() {
+            eyre::bail!("Invalid proxies:\n{}", errors.join("\n"));
+        }
+
+        Ok(proxies)
     }
 
-    pub fn get_random_account_with_filter<F>(&mut self, filter: F) -> Option<&mut Account>
-    where
-        F: Fn(&Account) -> bool,
-    {
+    /// Gives every account without a header profile a random one of the config, so a wallet
+    /// keeps presenting the same client to the Grass API. Returns `true` if any were assigned.
+    pub fn assign_header_profiles(&mut self, profiles: &[HeaderProfile]) -> bool {
         let mut rng = thread_rng();
+        let mut assigned = false;
 
-        self.0
+        for account in self
+            .0
             .iter_mut()
-            .filter(|account| filter(account))
-            .choose(&mut rng)
+            .filter(|a| a.get_header_profile().is_none())
+        {
+            if let Some(profile) = profiles.choose(&mut rng) {
+                account.set_header_profile(profile.clone());
+                assigned = true;
+            }
+        }
+
+        assigned
     }
 
     pub fn update(&self) {
@@ -87,3 +229,38 @@ impl Database {
         let _ = serde_json::to_writer_pretty(file, &self);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_label_lines() {
+        let wallet_pubkey = Pubkey::new_unique();
+        let label = parse_label(&format!(" {wallet_pubkey} , 10 ")).unwrap();
+
+        assert_eq!(label.wallet_pubkey, wallet_pubkey);
+        assert_eq!(label.priority, 10);
+        assert!(label.tags.is_empty());
+
+        let label = parse_label(&format!("{wallet_pubkey},,vip; batch-1 ;")).unwrap();
+
+        assert_eq!(label.priority, 0);
+        assert_eq!(label.tags, vec!["vip", "batch-1"]);
+    }
+
+    #[test]
+    fn rejects_malformed_label_lines() {
+        let wallet_pubkey = Pubkey::new_unique();
+
+        for line in [
+            wallet_pubkey.to_string(),
+            format!("{wallet_pubkey},1,vip,extra"),
+            format!("{wallet_pubkey},-1"),
+            format!("{wallet_pubkey},high"),
+            "not-a-wallet,1".to_string(),
+        ] {
+            assert!(parse_label(&line).is_err(), "`{line}` was accepted");
+        }
+    }
+}
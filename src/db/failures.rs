@@ -0,0 +1,172 @@
+use std::{
+    fmt::{self, Display},
+    fs::File,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use solana_client::client_error::ClientError;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::onchain::tx::TxFailed;
+
+use super::constants::FAILURES_FILE_PATH;
+
+/// Operation a wallet failed in, declared in the order the operations are retried.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Receipt,
+    Disperse,
+    Claim,
+    Forward,
+    Close,
+    Collect,
+    CollectAndClose,
+    Lifecycle,
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let step = match self {
+            Self::Receipt => "receipt",
+            Self::Disperse => "disperse",
+            Self::Claim => "claim",
+            Self::Forward => "forward",
+            Self::Close => "close",
+            Self::Collect => "collect",
+            Self::CollectAndClose => "collect_and_close",
+            Self::Lifecycle => "lifecycle",
+        };
+
+        f.write_str(step)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    Transaction,
+    Simulation,
+    InsufficientFunds,
+    Receipt,
+    Proxy,
+    Rpc,
+    Http,
+    Other,
+}
+
+impl ErrorClass {
+    fn classify(e: &eyre::Report, message: &str) -> Self {
+        let message = message.to_lowercase();
+
+        if e.downcast_ref::<TxFailed>().is_some() {
+            Self::Transaction
+        } else if message.contains("insufficient") || message.contains("not enough") {
+            Self::InsufficientFunds
+        } else if message.contains("simulation") {
+            Self::Simulation
+        } else if message.contains("receipt") || message.contains("proof") {
+            Self::Receipt
+        } else if message.contains("proxy") || message.contains("ip address") {
+            Self::Proxy
+        } else if e.chain().any(|e| e.is::<ClientError>()) || message.contains("send tx") {
+            Self::Rpc
+        } else if e.chain().any(|e| e.is::<reqwest::Error>()) || message.contains("http") {
+            Self::Http
+        } else {
+            Self::Other
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Failure {
+    pub address: String,
+    pub step: Step,
+    pub class: ErrorClass,
+    pub message: String,
+    pub signature: Option<String>,
+    pub failed_at: u64,
+}
+
+/// Last failure of every wallet and step, kept in the failures file until the wallet passes the
+/// step in a later run.
+pub struct FailureLog {
+    step: Step,
+    failures: Vec<Failure>,
+}
+
+impl FailureLog {
+    pub async fn read_all() -> eyre::Result<Vec<Failure>> {
+        match tokio::fs::read_to_string(FAILURES_FILE_PATH).await {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn read(step: Step) -> eyre::Result<Self> {
+        Ok(Self {
+            step,
+            failures: Self::read_all().await?,
+        })
+    }
+
+    pub fn record(&mut self, wallet_pubkey: &Pubkey, e: &eyre::Report) {
+        self.record_as(self.step, wallet_pubkey, e);
+    }
+
+    /// Records the failure under another step than the log's own, e.g. a forward made after a claim.
+    pub fn record_as(&mut self, step: Step, wallet_pubkey: &Pubkey, e: &eyre::Report) {
+        let address = wallet_pubkey.to_string();
+        let message = e
+            .chain()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join(": ");
+
+        let failure = Failure {
+            class: ErrorClass::classify(e, &message),
+            signature: e
+                .downcast_ref::<TxFailed>()
+                .map(|e| e.signature.to_string()),
+            address,
+            step,
+            message,
+            failed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+        };
+
+        self.remove(step, &failure.address);
+        self.failures.push(failure);
+        self.update();
+    }
+
+    /// Forgets the failure of the wallet in this step after it passed.
+    pub fn resolve(&mut self, wallet_pubkey: &Pubkey) {
+        self.resolve_as(self.step, wallet_pubkey);
+    }
+
+    /// Forgets the failure of the wallet in another step than the log's own.
+    pub fn resolve_as(&mut self, step: Step, wallet_pubkey: &Pubkey) {
+        if self.remove(step, &wallet_pubkey.to_string()) {
+            self.update();
+        }
+    }
+
+    fn remove(&mut self, step: Step, address: &str) -> bool {
+        let len = self.failures.len();
+
+        self.failures
+            .retain(|failure| failure.step != step || failure.address != address);
+
+        self.failures.len() != len
+    }
+
+    fn update(&self) {
+        let file = File::create(FAILURES_FILE_PATH).expect("Failures file must be valid");
+        let _ = serde_json::to_writer_pretty(file, &self.failures);
+    }
+}
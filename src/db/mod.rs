@@ -1,3 +1,6 @@
 pub mod account;
-mod constants;
+pub mod constants;
 pub mod database;
+pub mod failures;
+pub mod order;
+pub mod selection;
@@ -0,0 +1,67 @@
+use std::cmp::Reverse;
+
+use rand::{rngs::StdRng, seq::SliceRandom, thread_rng, RngCore, SeedableRng};
+
+use crate::config::{AccountOrdering, Config};
+
+use super::{account::Account, database::Database};
+
+/// Order the accounts of a run are processed in. Accounts are visited in a cycle, so one that
+/// still matches the filter after being processed is retried only after the others.
+pub struct AccountOrder {
+    indices: Vec<usize>,
+    cursor: usize,
+}
+
+impl AccountOrder {
+    pub fn from_config(db: &Database, config: &Config) -> Self {
+        let mut indices = (0..db.0.len()).collect::<Vec<_>>();
+
+        match config.account_ordering {
+            AccountOrdering::Random => {
+                let seed = match config.account_ordering_seed {
+                    0 => thread_rng().next_u64(),
+                    seed => seed,
+                };
+
+                indices.shuffle(&mut StdRng::seed_from_u64(seed));
+                tracing::info!("Account order: random, seed {seed}");
+            }
+            AccountOrdering::File => tracing::info!("Account order: file"),
+            AccountOrdering::Allocation => {
+                let version = config.claim_version;
+                indices.sort_by_key(|&index| {
+                    Reverse(db.0[index].get_claim(version).map_or(0, |c| c.allocation))
+                });
+                tracing::info!("Account order: allocation descending (version {version})");
+            }
+            AccountOrdering::Priority => {
+                indices.sort_by_key(|&index| Reverse(db.0[index].get_priority()));
+                tracing::info!("Account order: priority descending");
+            }
+        }
+
+        Self { indices, cursor: 0 }
+    }
+
+    /// Database indices of the accounts in processing order.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// Returns the next account matching the filter, continuing after the previous one.
+    pub fn next<'a, F>(&mut self, db: &'a mut Database, filter: F) -> Option<&'a mut Account>
+    where
+        F: Fn(&Account) -> bool,
+    {
+        let len = self.indices.len();
+
+        let position = (0..len)
+            .map(|offset| (self.cursor + offset) % len)
+            .find(|&position| filter(&db.0[self.indices[position]]))?;
+
+        self.cursor = position + 1;
+
+        db.0.get_mut(self.indices[position])
+    }
+}
@@ -0,0 +1,354 @@
+use std::{collections::HashSet, ops::RangeInclusive, str::FromStr};
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{config::Config, utils::files::read_file_lines};
+
+use super::{account::Account, database::Database};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Claimed,
+    Unclaimed,
+    Forwarded,
+    Unforwarded,
+    Closed,
+    Open,
+    Collected,
+    Uncollected,
+    Funded,
+    Unfunded,
+}
+
+impl FromStr for Status {
+    type Err = eyre::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let status = match s {
+            "claimed" => Self::Claimed,
+            "unclaimed" => Self::Unclaimed,
+            "forwarded" => Self::Forwarded,
+            "unforwarded" => Self::Unforwarded,
+            "closed" => Self::Closed,
+            "open" => Self::Open,
+            "collected" => Self::Collected,
+            "uncollected" => Self::Uncollected,
+            "funded" => Self::Funded,
+            "unfunded" => Self::Unfunded,
+            _ => eyre::bail!("Unknown status `{s}`"),
+        };
+
+        Ok(status)
+    }
+}
+
+impl Status {
+    fn matches(&self, account: &Account, version: u32) -> bool {
+        match self {
+            Self::Claimed => account.get_claimed(version),
+            Self::Unclaimed => !account.get_claimed(version),
+            Self::Forwarded => account.get_forwarded_grass(),
+            Self::Unforwarded => !account.get_forwarded_grass(),
+            Self::Closed => account.get_closed_ata(),
+            Self::Open => !account.get_closed_ata(),
+            Self::Collected => account.get_collected_sol(),
+            Self::Uncollected => !account.get_collected_sol(),
+            Self::Funded => account.get_funded(),
+            Self::Unfunded => !account.get_funded(),
+        }
+    }
+}
+
+/// Accounts an operation is limited to. Addresses and index ranges pick wallets, tags pick
+/// groups of them and every status has to match. An empty selection matches every account.
+#[derive(Default)]
+pub struct Selection {
+    spec: String,
+    addresses: HashSet<Pubkey>,
+    ranges: Vec<RangeInclusive<usize>>,
+    tags: HashSet<String>,
+    statuses: Vec<Status>,
+}
+
+impl Selection {
+    /// Parses terms separated by commas or whitespace:
+    /// - `<address>` or `@<file>` with one address per line
+    /// - `<index>` or `<from>-<to>`, 1-based positions in the database
+    /// - `tag:<tag>`
+    /// - `status:<status>`, e.g. `status:unclaimed` or `status:open`
+    pub async fn parse(spec: &str) -> eyre::Result<Self> {
+        let mut selection = Self {
+            spec: spec.trim().to_string(),
+            ..Default::default()
+        };
+
+        let terms = spec
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|term| !term.is_empty());
+
+        for term in terms {
+            if let Some(tag) = term.strip_prefix("tag:") {
+                selection.tags.insert(tag.to_string());
+            } else if let Some(status) = term.strip_prefix("status:") {
+                selection.statuses.push(status.parse()?);
+            } else if let Some(path) = term.strip_prefix('@') {
+                for line in read_file_lines(path)
+                    .await
+                    .map_err(|e| eyre::eyre!("Failed to read `{path}`: {e}"))?
+                {
+                    selection.addresses.insert(parse_address(&line)?);
+                }
+            } else if term.starts_with(|c: char| c.is_ascii_digit()) && term.len() < 32 {
+                selection.ranges.push(parse_range(term)?);
+            } else {
+                selection.addresses.insert(parse_address(term)?);
+            }
+        }
+
+        Ok(selection)
+    }
+
+    /// Selection of exactly the given wallets, `spec` only naming it in the logs.
+    pub fn from_addresses(spec: &str, addresses: impl IntoIterator<Item = Pubkey>) -> Self {
+        Self {
+            spec: spec.to_string(),
+            addresses: addresses.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+            && self.ranges.is_empty()
+            && self.tags.is_empty()
+            && self.statuses.is_empty()
+    }
+
+    pub fn get_spec(&self) -> &str {
+        &self.spec
+    }
+
+    fn matches(&self, position: usize, account: &Account, version: u32) -> bool {
+        let picked = (self.addresses.is_empty() && self.ranges.is_empty())
+            || self.addresses.contains(&account.get_pubkey())
+            || self.ranges.iter().any(|range| range.contains(&position));
+
+        let tagged =
+            self.tags.is_empty() || account.get_tags().iter().any(|t| self.tags.contains(t));
+
+        picked && tagged && self.statuses.iter().all(|s| s.matches(account, version))
+    }
+
+    fn pick(&self, accounts: &[Account], version: u32) -> HashSet<Pubkey> {
+        accounts
+            .iter()
+            .enumerate()
+            .filter(|(index, account)| self.matches(index + 1, account, version))
+            .map(|(_, account)| account.get_pubkey())
+            .collect()
+    }
+
+    /// Resolves the selection against the database at the start of an operation.
+    pub fn select(&self, db: &Database, config: &Config) -> SelectedAccounts {
+        if self.is_empty() {
+            return SelectedAccounts(None);
+        }
+
+        for range in self.ranges.iter().filter(|range| *range.end() > db.0.len()) {
+            tracing::warn!(
+                "Range {}-{} goes past the last of {} accounts",
+                range.start(),
+                range.end(),
+                db.0.len()
+            );
+        }
+
+        let pubkeys = self.pick(&db.0, config.claim_version);
+
+        tracing::info!(
+            "Selection `{}`: {} | {} accounts",
+            self.spec,
+            pubkeys.len(),
+            db.0.len()
+        );
+
+        SelectedAccounts(Some(pubkeys))
+    }
+}
+
+/// Accounts picked by a selection, `None` standing for all of them.
+pub struct SelectedAccounts(Option<HashSet<Pubkey>>);
+
+impl SelectedAccounts {
+    pub fn contains(&self, account: &Account) -> bool {
+        match &self.0 {
+            Some(pubkeys) => pubkeys.contains(&account.get_pubkey()),
+            None => true,
+        }
+    }
+}
+
+fn parse_address(address: &str) -> eyre::Result<Pubkey> {
+    Pubkey::from_str(address).map_err(|e| eyre::eyre!("Invalid address `{address}`: {e}"))
+}
+
+fn parse_range(term: &str) -> eyre::Result<RangeInclusive<usize>> {
+    let parse = |index: &str| {
+        index
+            .parse::<usize>()
+            .map_err(|e| eyre::eyre!("Invalid index `{index}` in `{term}`: {e}"))
+    };
+
+    let range = match term.split_once('-') {
+        Some((from, to)) => parse(from)?..=parse(to)?,
+        None => parse(term)?..=parse(term)?,
+    };
+
+    if *range.start() == 0 || range.start() > range.end() {
+        eyre::bail!("Invalid range `{term}`, positions start at 1");
+    }
+
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{signature::Keypair, signer::Signer};
+
+    use super::*;
+
+    fn accounts(count: usize) -> Vec<Account> {
+        (0..count)
+            .map(|_| {
+                let private_key = Keypair::new().to_base58_string();
+                Account::new(&private_key, None, &Pubkey::new_unique().to_string(), None)
+            })
+            .collect()
+    }
+
+    fn pubkeys(accounts: &[Account], positions: &[usize]) -> HashSet<Pubkey> {
+        positions
+            .iter()
+            .map(|position| accounts[position - 1].get_pubkey())
+            .collect()
+    }
+
+    async fn pick(spec: &str, accounts: &[Account]) -> HashSet<Pubkey> {
+        Selection::parse(spec).await.unwrap().pick(accounts, 1)
+    }
+
+    #[tokio::test]
+    async fn empty_selection_picks_everything() {
+        let selection = Selection::parse(" , ").await.unwrap();
+
+        assert!(selection.is_empty());
+        assert_eq!(selection.pick(&accounts(3), 1).len(), 3);
+    }
+
+    #[tokio::test]
+    async fn picks_indices_and_ranges() {
+        let accounts = accounts(6);
+
+        assert_eq!(
+            pick("2, 4-5", &accounts).await,
+            pubkeys(&accounts, &[2, 4, 5])
+        );
+        assert_eq!(pick("1-1 6", &accounts).await, pubkeys(&accounts, &[1, 6]));
+    }
+
+    #[tokio::test]
+    async fn out_of_bounds_ranges_pick_existing_accounts_only() {
+        let accounts = accounts(3);
+
+        assert_eq!(pick("2-10", &accounts).await, pubkeys(&accounts, &[2, 3]));
+        assert!(pick("7", &accounts).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_ranges() {
+        for spec in ["0", "0-3", "5-2", "1-", "-3", "1-2-3", "2x"] {
+            assert!(
+                Selection::parse(spec).await.is_err(),
+                "`{spec}` was accepted"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn picks_addresses_and_address_files() {
+        let accounts = accounts(4);
+        let path = std::env::temp_dir().join(format!("selection-{}.txt", Pubkey::new_unique()));
+
+        tokio::fs::write(
+            &path,
+            format!(
+                "{}\n\n{}\n",
+                accounts[1].get_pubkey(),
+                accounts[3].get_pubkey()
+            ),
+        )
+        .await
+        .unwrap();
+
+        let spec = format!("{} @{}", accounts[0].get_pubkey(), path.display());
+        let picked = pick(&spec, &accounts).await;
+
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert_eq!(picked, pubkeys(&accounts, &[1, 2, 4]));
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_addresses_and_missing_files() {
+        for spec in ["not-an-address", "@/nonexistent/selection.txt"] {
+            assert!(
+                Selection::parse(spec).await.is_err(),
+                "`{spec}` was accepted"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn picks_tags_within_addresses_and_ranges() {
+        let mut accounts = accounts(4);
+        accounts[0].set_tags(vec!["vip".to_string()]);
+        accounts[2].set_tags(vec!["vip".to_string(), "batch-1".to_string()]);
+        accounts[3].set_tags(vec!["batch-1".to_string()]);
+
+        assert_eq!(
+            pick("tag:vip", &accounts).await,
+            pubkeys(&accounts, &[1, 3])
+        );
+        assert_eq!(
+            pick("tag:vip tag:batch-1", &accounts).await,
+            pubkeys(&accounts, &[1, 3, 4])
+        );
+        assert_eq!(
+            pick("2-4 tag:vip", &accounts).await,
+            pubkeys(&accounts, &[3])
+        );
+        assert!(pick("tag:unknown", &accounts).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn statuses_all_have_to_match() {
+        let mut accounts = accounts(3);
+        accounts[0].set_claimed(1, None);
+        accounts[1].set_claimed(1, None);
+        accounts[1].set_closed_ata(true);
+
+        assert_eq!(
+            pick("status:unclaimed", &accounts).await,
+            pubkeys(&accounts, &[3])
+        );
+        assert_eq!(
+            pick("status:claimed,status:open", &accounts).await,
+            pubkeys(&accounts, &[1])
+        );
+        assert_eq!(
+            pick("1-2 status:closed", &accounts).await,
+            pubkeys(&accounts, &[2])
+        );
+        assert!(Selection::parse("status:pending").await.is_err());
+    }
+}
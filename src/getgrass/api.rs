@@ -1,18 +1,47 @@
-use reqwest::{Method, Proxy};
+use reqwest::{
+    header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, ORIGIN, REFERER, USER_AGENT},
+    Method,
+};
 
-use crate::utils::fetch::{send_http_request, RequestParams};
+use crate::{
+    config::HeaderProfile,
+    utils::fetch::{send_http_request, RequestParams},
+};
 
 use super::{
     constants::CLAIM_AIRDROP_RECEIPT,
-    schemas::{GrassApiResponse, Receipt},
     typedefs::{Cluster, ReceiptQuery},
 };
 
+fn get_headers(profile: &HeaderProfile) -> eyre::Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    for (name, value) in [
+        (USER_AGENT, &profile.user_agent),
+        (ORIGIN, &profile.origin),
+        (REFERER, &profile.referer),
+        (ACCEPT_LANGUAGE, &profile.accept_language),
+    ] {
+        if value.is_empty() {
+            continue;
+        }
+
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| eyre::eyre!("Invalid `{name}` header `{value}`: {e}"))?;
+
+        headers.insert(name, value);
+    }
+
+    Ok(headers)
+}
+
+/// Returns the raw response body, so it can be cached as is.
 pub async fn get_receipt(
     wallet_address: &str,
     cluster: Cluster,
-    proxy: Option<&Proxy>,
-) -> eyre::Result<GrassApiResponse<Receipt>> {
+    proxy: Option<&str>,
+    header_profile: Option<&HeaderProfile>,
+) -> eyre::Result<serde_json::Value> {
     let query = ReceiptQuery::to_string(wallet_address, cluster)
         .expect("Failed to stringify receipt query");
 
@@ -24,10 +53,10 @@ pub async fn get_receipt(
         body: None::<serde_json::Value>,
         query_args: Some(query_args),
         proxy,
-        headers: None,
+        headers: header_profile.map(get_headers).transpose()?,
     };
 
-    let response_body = send_http_request::<GrassApiResponse<Receipt>>(request_params).await?;
+    let response_body = send_http_request::<serde_json::Value>(request_params).await?;
 
     Ok(response_body)
 }
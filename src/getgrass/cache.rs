@@ -0,0 +1,70 @@
+use std::{collections::BTreeMap, fs::File};
+
+use serde::{Deserialize, Serialize};
+
+use super::{constants::RECEIPTS_FILE_PATH, typedefs::Cluster};
+
+/// Receipt as returned by the Grass API together with the parts the claim instruction needs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedReceipt {
+    pub raw: serde_json::Value,
+    pub version_number: u32,
+    pub proof: Vec<[u8; 32]>,
+    pub allocation: u64,
+}
+
+/// Receipts stored on disk, keyed by wallet address and cluster. Lookups only match the receipt of
+/// the requested distributor version, so one left from an earlier version is fetched again.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ReceiptCache(BTreeMap<String, CachedReceipt>);
+
+impl ReceiptCache {
+    fn key(wallet_address: &str, cluster: Cluster) -> String {
+        format!("{wallet_address}:{cluster}")
+    }
+
+    pub async fn read() -> eyre::Result<Self> {
+        match tokio::fs::read_to_string(RECEIPTS_FILE_PATH).await {
+            Ok(contents) => Ok(serde_json::from_str::<Self>(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn get(
+        &self,
+        wallet_address: &str,
+        cluster: Cluster,
+        version: u32,
+    ) -> Option<&CachedReceipt> {
+        self.0
+            .get(&Self::key(wallet_address, cluster))
+            .filter(|receipt| receipt.version_number == version)
+    }
+
+    pub fn contains(&self, wallet_address: &str, cluster: Cluster, version: u32) -> bool {
+        self.get(wallet_address, cluster, version).is_some()
+    }
+
+    pub fn insert(&mut self, wallet_address: &str, cluster: Cluster, receipt: CachedReceipt) {
+        self.0.insert(Self::key(wallet_address, cluster), receipt);
+    }
+
+    /// Entries of a single cluster, keyed by wallet address.
+    pub fn into_cluster_entries(self, cluster: Cluster) -> Vec<(String, CachedReceipt)> {
+        let suffix = format!(":{cluster}");
+
+        self.0
+            .into_iter()
+            .filter_map(|(key, receipt)| {
+                key.strip_suffix(&suffix)
+                    .map(|wallet_address| (wallet_address.to_string(), receipt))
+            })
+            .collect()
+    }
+
+    pub fn update(&self) {
+        let file = File::create(RECEIPTS_FILE_PATH).expect("Receipts file must be writable");
+        let _ = serde_json::to_writer_pretty(file, &self);
+    }
+}
@@ -1 +1,2 @@
 pub const CLAIM_AIRDROP_RECEIPT: &str = "https://api.getgrass.io/airdropClaimReceipt";
+pub const RECEIPTS_FILE_PATH: &str = "data/receipts.json";
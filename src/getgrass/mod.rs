@@ -1,4 +1,5 @@
 pub mod api;
+pub mod cache;
 mod constants;
 pub mod schemas;
 pub mod typedefs;
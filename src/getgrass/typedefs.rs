@@ -1,5 +1,6 @@
 use serde::Serialize;
 
+#[derive(Clone, Copy)]
 pub enum Cluster {
     Mainnet,
 }
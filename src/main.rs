@@ -8,11 +8,28 @@ mod modules;
 mod onchain;
 mod utils;
 
+/// Account selection passed as `--select <spec>` or `--select=<spec>`.
+fn get_selection_arg() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        if arg == "--select" {
+            return args.next();
+        }
+
+        if let Some(spec) = arg.strip_prefix("--select=") {
+            return Some(spec.to_string());
+        }
+    }
+
+    None
+}
+
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
     let _guard = init_default_logger();
 
-    if let Err(e) = menu().await {
+    if let Err(e) = menu(get_selection_arg()).await {
         tracing::error!("Execution stopped with an unexpected error: {e}");
     }
 
@@ -1,152 +1,253 @@
-use std::{str::FromStr, time::Duration};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
 
 use borsh::BorshDeserialize;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig, instruction::Instruction, native_token::lamports_to_sol,
-    pubkey::Pubkey, signature::Keypair, signer::Signer, transaction::Transaction,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    native_token::lamports_to_sol,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+
+use super::{
+    forwarder::{get_forward_ixs, validate_cex_memo},
+    prefetch::prefetch_leased_accounts,
+    receipts::{get_claim_receipt, get_offline_skipped},
+    report::{get_tx_fee, Amounts, RunReport},
 };
 
 use crate::{
     config::Config,
-    db::{account::Account, database::Database},
-    getgrass::{
-        api::get_receipt,
-        schemas::{ClaimProofEntry, GrassApiResponse, Receipt},
-        typedefs::Cluster,
+    db::{
+        account::Account,
+        database::Database,
+        failures::{FailureLog, Step},
+        order::AccountOrder,
+        selection::Selection,
     },
+    getgrass::cache::{CachedReceipt, ReceiptCache},
     onchain::{
+        cache::AccountCache,
         constants::{CLAIM_PROGRAM_ID, GRASS_PUBKEY, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID},
         derive::{derive_ata, derive_claim_status, derive_merkle_distributor},
+        fee_payer::FeePayerPool,
         ixs::Instructions,
-        state::ClaimStatus,
+        state::{ClaimStatus, CLAIM_STATUS_LEN},
+        transport::RpcPool,
         tx::send_and_confirm_tx,
         typedefs::{ClaimArgs, CreateAtaArgs},
     },
-    utils::misc::{pretty_sleep, swap_ip_address},
+    utils::{
+        misc::pretty_sleep,
+        proxy_pool::{ProxyLease, ProxyPool},
+    },
 };
 
-pub async fn claim_grass(mut db: Database, config: &Config) -> eyre::Result<()> {
-    let provider = RpcClient::new_with_timeout_and_commitment(
-        config.solana_rpc_url.clone(),
-        Duration::from_secs(60),
-        CommitmentConfig::processed(),
-    );
+/// Forward that failed after its claim confirmed, reported apart from the claim along with what
+/// the claim moved.
+#[derive(Debug)]
+struct ForwardFailed {
+    amounts: Amounts,
+}
 
-    while let Some(account) = db.get_random_account_with_filter(|a| !a.get_claimed()) {
-        if let Err(e) = process_account(&provider, account, config).await {
-            tracing::error!("{}", e);
-        } else {
-            account.set_claimed(true);
-            db.update();
-        };
+impl Display for ForwardFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Forward after the claim failed")
+    }
+}
 
-        pretty_sleep(config.claim_sleep_range).await;
+pub async fn claim_grass(
+    mut db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let mut proxies = ProxyPool::new(&db, config).await?;
+    let rpc = RpcPool::with_proxies(config, proxies.urls())?;
+    let provider = rpc.direct();
+
+    let mut fee_payers = FeePayerPool::from_config(config)?;
+    let mut order = AccountOrder::from_config(&db, config);
+    let mut failures = FailureLog::read(Step::Claim).await?;
+    let mut report = RunReport::new(Step::Claim);
+
+    let version = config.claim_version;
+    let mut receipts = ReceiptCache::read().await?;
+    let mut deferred = get_offline_skipped(&db, &receipts, config);
+
+    if db.assign_header_profiles(&config.header_profiles) {
+        db.update();
     }
 
-    Ok(())
-}
+    let mut cache = AccountCache::new(provider, config);
+    prefetch_leased_accounts(
+        &mut cache,
+        &rpc,
+        &proxies,
+        db.0.iter()
+            .filter(|a| selected.contains(a) && !a.get_claimed(version)),
+        config,
+    )
+    .await?;
+
+    while let Some(account) = order.next(&mut db, |a| {
+        selected.contains(a) && !a.get_claimed(version) && !deferred.contains(&a.get_pubkey())
+    }) {
+        if proxies.is_exhausted() {
+            tracing::error!("Every proxy is out of rotation, stopping");
+            break;
+        }
 
-fn prepare_proof(claim_proof_json: &str) -> Vec<[u8; 32]> {
-    if let Ok(claim_proof_array) = serde_json::from_str::<Vec<ClaimProofEntry>>(claim_proof_json) {
-        claim_proof_array
-            .into_iter()
-            .filter_map(|entry| {
-                if entry.data.type_ != "Buffer" {
-                    return None;
-                }
+        let fee_payer = match fee_payers.next(provider).await {
+            Ok(fee_payer) => fee_payer,
+            Err(e) => {
+                tracing::error!("{}", e);
+                break;
+            }
+        };
 
-                let data_bytes = entry.data.data;
+        let lease = match proxies.get(&account.get_pubkey().to_string(), account.get_proxy()) {
+            Ok(lease) => lease,
+            Err(e) => {
+                tracing::error!("{}", e);
+                break;
+            }
+        };
 
-                if data_bytes.len() != 32 {
-                    return None;
+        // RPC calls of the wallet go through the proxy its Grass API requests use
+        let account_provider = rpc.get(lease.as_ref().map(|lease| lease.url.as_str()));
+        cache.set_provider(account_provider);
+
+        match process_account(
+            account_provider,
+            &mut cache,
+            &mut receipts,
+            &mut proxies,
+            lease.as_ref(),
+            account,
+            fee_payer.as_ref(),
+            config,
+        )
+        .await
+        {
+            Ok(amounts) => {
+                report.processed(&account.get_pubkey(), amounts);
+
+                if account.get_forwarded_grass() {
+                    failures.resolve_as(Step::Forward, &account.get_pubkey());
                 }
 
-                let mut buffer = [0u8; 32];
-                buffer.copy_from_slice(&data_bytes);
-                Some(buffer)
-            })
-            .collect()
-    } else {
-        Vec::new()
+                if account.get_claimed(version) {
+                    failures.resolve(&account.get_pubkey());
+                } else {
+                    deferred.insert(account.get_pubkey());
+                }
+            }
+            Err(e) => match e.downcast_ref::<ForwardFailed>() {
+                // the claim went through, so only the forward is left to retry
+                Some(ForwardFailed { amounts }) => {
+                    tracing::error!("{:#}", e);
+                    failures.resolve(&account.get_pubkey());
+                    failures.record_as(Step::Forward, &account.get_pubkey(), &e);
+                    report.failed_after(&account.get_pubkey(), *amounts, &e);
+                }
+                None => {
+                    tracing::error!("{}", e);
+                    failures.record(&account.get_pubkey(), &e);
+                    report.failed(&account.get_pubkey(), &e);
+                }
+            },
+        };
+
+        db.update();
+
+        pretty_sleep(config.claim_sleep_range).await;
     }
+
+    report.finish()
 }
 
-fn extract_version_and_proof(
-    receipt: &GrassApiResponse<Receipt>,
-) -> eyre::Result<(u32, Vec<[u8; 32]>, u64)> {
-    let result = receipt
-        .result
-        .as_ref()
-        .ok_or_else(|| eyre::eyre!("Receipt result is missing"))?;
-    let data = result
-        .data
-        .as_ref()
-        .ok_or_else(|| eyre::eyre!("Data is missing in the receipt result"))?;
-    let version_number = data
-        .version_number
-        .as_ref()
-        .ok_or_else(|| eyre::eyre!("Version number is missing in the receipt data"))?;
-    let claim_proof = data
-        .claim_proof
-        .as_ref()
-        .ok_or_else(|| eyre::eyre!("Claim proof is missing in the receipt data"))?;
-    let proof = prepare_proof(claim_proof);
-
-    let allocation = data
-        .allocation
-        .as_ref()
-        .ok_or_else(|| eyre::eyre!("Allocation is missing in the receipt data"))?;
-    Ok((*version_number, proof, *allocation))
+pub(super) async fn get_sent_allocation(
+    cache: &mut AccountCache<'_>,
+    claim_status_pubkey: &Pubkey,
+) -> eyre::Result<Option<(u64, u64)>> {
+    match cache.get_account(claim_status_pubkey).await? {
+        Some(claim_status_account) => Ok(Some(read_sent_allocation(&claim_status_account.data)?)),
+        None => Ok(None),
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn get_ixs(
-    provider: &RpcClient,
+/// Total and sent allocation stored in the data of a ClaimStatus PDA.
+pub(super) fn read_sent_allocation(data: &[u8]) -> eyre::Result<(u64, u64)> {
+    let claim_status = ClaimStatus::deserialize(&mut &data[8..])?;
+    Ok((claim_status.allocation, claim_status.sent_allocation))
+}
+
+/// On-chain state deciding which accounts a claim transaction has to create and fund.
+pub(super) struct ClaimAccounts {
+    pub token_ata_exist: bool,
+    pub claim_status_exist: bool,
+    pub wallet_balance: u64,
+}
+
+pub(super) async fn get_claim_accounts(
+    cache: &mut AccountCache<'_>,
+    version_number: u32,
+    wallet_pubkey: &Pubkey,
+) -> eyre::Result<ClaimAccounts> {
+    let (merkle_distributor_pubkey, _) = derive_merkle_distributor(version_number);
+    let (claim_status_pubkey, _) = derive_claim_status(wallet_pubkey, &merkle_distributor_pubkey);
+    let (wallet_token_ata, _) = derive_ata(wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
+
+    Ok(ClaimAccounts {
+        token_ata_exist: cache.exists(&wallet_token_ata).await?,
+        claim_status_exist: cache.exists(&claim_status_pubkey).await?,
+        wallet_balance: cache.get_balance(wallet_pubkey).await?,
+    })
+}
+
+pub(super) async fn get_ixs(
+    cache: &mut AccountCache<'_>,
     version_number: u32,
     proof: Vec<[u8; 32]>,
     allocation: u64,
     wallet_pubkey: &Pubkey,
-    cex_pubkey: &Pubkey,
     payer_pubkey: &Pubkey,
-    config: &Config,
 ) -> eyre::Result<Option<Vec<Instruction>>> {
     let (merkle_distributor_pubkey, _) = derive_merkle_distributor(version_number);
 
     let (claim_status_pubkey, _) = derive_claim_status(wallet_pubkey, &merkle_distributor_pubkey);
 
-    if let Ok(claim_status_data) = provider.get_account_data(&claim_status_pubkey).await {
-        let claim_status = ClaimStatus::deserialize(&mut &claim_status_data[8..])?;
-
-        if claim_status.allocation == claim_status.sent_allocation {
-            tracing::info!("Already claimed");
-            return Ok(None);
-        }
-    }
-
     let (token_vault, _) = derive_ata(&merkle_distributor_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
 
     let mut ixs = vec![];
 
     let (wallet_token_ata, _) = derive_ata(wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
-    let token_ata_exist = provider.get_account_data(&wallet_token_ata).await.is_ok();
+    let claim_accounts = get_claim_accounts(cache, version_number, wallet_pubkey).await?;
 
-    if !token_ata_exist {
+    if !claim_accounts.token_ata_exist {
         let create_ata_args = CreateAtaArgs {
             funding_address: *payer_pubkey,
             associated_account_address: wallet_token_ata,
             wallet_address: *wallet_pubkey,
             token_mint_address: GRASS_PUBKEY,
             token_program_id: TOKEN_PROGRAM_ID,
-            instruction: 0,
+            instruction: 1,
         };
 
         ixs.push(Instructions::create_ata(create_ata_args));
     }
 
-    let rent = provider.get_minimum_balance_for_rent_exemption(64).await?;
+    let rent = cache.get_rent(CLAIM_STATUS_LEN).await?;
 
-    let wallet_balance = provider.get_balance(wallet_pubkey).await?;
+    let wallet_balance = claim_accounts.wallet_balance;
 
     if wallet_balance < rent {
         if payer_pubkey == wallet_pubkey {
@@ -183,110 +284,254 @@ async fn get_ixs(
 
     ixs.push(claim_ix);
 
-    if config.withdraw_to_cex {
-        let (cex_token_ata, _) = derive_ata(cex_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
-        let cex_token_ata_exist = provider.get_account_data(&cex_token_ata).await.is_ok();
-
-        if !cex_token_ata_exist {
-            let create_ata_args = CreateAtaArgs {
-                funding_address: *payer_pubkey,
-                associated_account_address: cex_token_ata,
-                wallet_address: *cex_pubkey,
-                token_mint_address: GRASS_PUBKEY,
-                token_program_id: TOKEN_PROGRAM_ID,
-                instruction: 0,
-            };
-
-            ixs.push(Instructions::create_ata(create_ata_args));
-        }
+    Ok(Some(ixs))
+}
 
-        let transfer_ix = spl_token::instruction::transfer_checked(
-            &TOKEN_PROGRAM_ID,
-            &wallet_token_ata,
-            &GRASS_PUBKEY,
-            &cex_token_ata,
-            wallet_pubkey,
-            &[wallet_pubkey],
-            allocation,
-            9u8,
-        )?;
-
-        ixs.push(transfer_ix);
-    }
+/// Returns the signature of the confirmed transaction and the fee the wallet paid for it.
+async fn send_ixs(
+    provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
+    instructions: &[Instruction],
+    payer_kp: &Keypair,
+    signing_keypairs: &[&Keypair],
+    wallet_pubkey: &Pubkey,
+) -> eyre::Result<(Signature, u64)> {
+    cache.invalidate(instructions);
 
-    Ok(Some(ixs))
+    let (recent_blockhash, _) = provider
+        .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+        .await?;
+
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer_kp.pubkey()),
+        signing_keypairs,
+        recent_blockhash,
+    );
+
+    let fee = get_tx_fee(provider, &tx, wallet_pubkey).await;
+    let signature = send_and_confirm_tx(provider, tx, &recent_blockhash).await?;
+
+    Ok((signature, fee))
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn process_account(
     provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
+    receipts: &mut ReceiptCache,
+    proxies: &mut ProxyPool,
+    lease: Option<&ProxyLease>,
     account: &mut Account,
+    fee_payer: Option<&Keypair>,
     config: &Config,
-) -> eyre::Result<()> {
+) -> eyre::Result<Option<Amounts>> {
     let wallet = account.keypair();
     let wallet_pubkey = account.get_pubkey();
     let cex_pubkey = Pubkey::from_str(account.get_cex_address()).expect("Invalid CEX address");
-    let proxy = account.proxy();
 
     tracing::info!("Wallet address: `{}`", wallet.pubkey());
 
-    if config.mobile_proxies {
-        tracing::info!("Changing IP address");
-        swap_ip_address(&config.swap_ip_link).await?;
+    if config.withdraw_to_cex {
+        validate_cex_memo(account.get_cex_memo(), config)?;
     }
 
-    let receipt = get_receipt(&wallet_pubkey.to_string(), Cluster::Mainnet, proxy.as_ref()).await?;
-    let (version_number, proof, allocation) = match extract_version_and_proof(&receipt) {
-        Ok(data) => data,
-        Err(e) => {
-            tracing::error!("{}", e);
-            account.set_claimed(true);
-            return Ok(());
+    let CachedReceipt {
+        version_number,
+        proof,
+        allocation,
+        ..
+    } = match get_claim_receipt(receipts, proxies, lease, account, config).await? {
+        Some(receipt) => receipt,
+        None => {
+            tracing::warn!("No receipt for version {}, skipping", config.claim_version);
+            return Ok(None);
         }
     };
 
-    let alloc = (allocation as f64) / 10f64.powi(9);
+    account.set_allocation(version_number, allocation);
+
+    if version_number != config.claim_version {
+        tracing::warn!(
+            "Receipt is issued for distributor version {}, but version {} is targeted",
+            version_number,
+            config.claim_version
+        );
+        return Ok(None);
+    }
+
+    let (merkle_distributor_pubkey, _) = derive_merkle_distributor(version_number);
+    let (claim_status_pubkey, _) = derive_claim_status(&wallet_pubkey, &merkle_distributor_pubkey);
+
+    let (total_allocation, sent_allocation) =
+        match get_sent_allocation(cache, &claim_status_pubkey).await? {
+            Some(status) => status,
+            None => (allocation, 0),
+        };
+
+    account.set_sent_allocation(version_number, sent_allocation);
+
+    if sent_allocation >= total_allocation {
+        tracing::info!("Already claimed");
+        account.set_claimed(version_number, None);
+        return Ok(None);
+    }
 
-    account.set_allocation(alloc);
-    tracing::info!("Amount to claim: {} GRASS", alloc);
+    let remainder = (total_allocation - sent_allocation) as f64 / 10f64.powi(9);
 
-    let payer_kp = match config.use_external_fee_pay {
-        true => Keypair::from_base58_string(&config.external_fee_payer_pk),
-        false => wallet.insecure_clone(),
+    tracing::info!("Amount to claim: {} GRASS", remainder);
+
+    let payer_kp = match fee_payer {
+        Some(fee_payer) => fee_payer.insecure_clone(),
+        None => wallet.insecure_clone(),
     };
 
-    let signing_keypairs = match config.use_external_fee_pay {
-        true => vec![&payer_kp, &wallet],
-        false => vec![&wallet],
+    let signing_keypairs = match fee_payer {
+        Some(_) => vec![&payer_kp, &wallet],
+        None => vec![&wallet],
     };
 
+    let (wallet_token_ata, _) = derive_ata(&wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
+
+    let pre_claim_balance = cache
+        .get_token_balance(&wallet_token_ata)
+        .await?
+        .unwrap_or(0);
+
     let instructions = match get_ixs(
-        provider,
+        cache,
         version_number,
         proof,
         allocation,
         &wallet_pubkey,
-        &cex_pubkey,
         &payer_kp.pubkey(),
-        config,
     )
     .await?
     {
         Some(ixs) => ixs,
-        None => return Ok(()),
+        None => return Ok(None),
     };
 
-    let (recent_blockhash, _) = provider
-        .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
-        .await?;
-
-    let tx = Transaction::new_signed_with_payer(
+    let claimed_before = sent_allocation;
+    let (signature, fee) = send_ixs(
+        provider,
+        cache,
         &instructions,
-        Some(&payer_kp.pubkey()),
+        &payer_kp,
         &signing_keypairs,
-        recent_blockhash,
+        &wallet_pubkey,
+    )
+    .await?;
+
+    // the claim is final at this point, so it's recorded before anything else can fail
+    let mut amounts = Amounts {
+        fees_paid: fee,
+        ..Default::default()
+    };
+
+    account.set_claim_signature(version_number, signature.to_string());
+    account.set_closed_ata(false);
+    account.set_forwarded_grass(false);
+
+    match get_sent_allocation(cache, &claim_status_pubkey).await {
+        Ok(Some((total_allocation, sent_allocation))) => {
+            account.set_sent_allocation(version_number, sent_allocation);
+
+            amounts.grass_claimed = sent_allocation.saturating_sub(claimed_before);
+
+            if sent_allocation >= total_allocation {
+                account.set_claimed(version_number, Some(signature.to_string()));
+            } else {
+                tracing::info!(
+                    "Partially claimed: {} of {} GRASS",
+                    sent_allocation as f64 / 10f64.powi(9),
+                    total_allocation as f64 / 10f64.powi(9)
+                );
+            }
+        }
+        Ok(None) => tracing::warn!("ClaimStatus PDA is missing after the claim"),
+        Err(e) => tracing::warn!("Failed to read ClaimStatus PDA after the claim: {}", e),
+    }
+
+    if config.withdraw_to_cex {
+        if let Err(e) = forward_received(
+            provider,
+            cache,
+            account,
+            &cex_pubkey,
+            pre_claim_balance,
+            &payer_kp,
+            &signing_keypairs,
+            &mut amounts,
+            config,
+        )
+        .await
+        {
+            return Err(e.wrap_err(ForwardFailed { amounts }));
+        }
+    }
+
+    Ok(Some(amounts))
+}
+
+/// Forwards the GRASS that arrived with the claim to the account's CEX address.
+#[allow(clippy::too_many_arguments)]
+async fn forward_received(
+    provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
+    account: &mut Account,
+    cex_pubkey: &Pubkey,
+    pre_claim_balance: u64,
+    payer_kp: &Keypair,
+    signing_keypairs: &[&Keypair],
+    amounts: &mut Amounts,
+    config: &Config,
+) -> eyre::Result<()> {
+    let wallet_pubkey = account.get_pubkey();
+    let (wallet_token_ata, _) = derive_ata(&wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
+
+    let post_claim_balance = cache
+        .get_token_balance(&wallet_token_ata)
+        .await?
+        .ok_or_else(|| eyre::eyre!("Grass ATA is missing after the claim"))?;
+    let received = post_claim_balance.saturating_sub(pre_claim_balance);
+
+    if received == 0 {
+        tracing::warn!("No GRASS arrived with the claim, nothing to forward");
+        return Ok(());
+    }
+
+    tracing::info!(
+        "Forwarding {} GRASS to `{}`",
+        received as f64 / 10f64.powi(9),
+        cex_pubkey
     );
 
-    send_and_confirm_tx(provider, tx, &recent_blockhash).await?;
+    let instructions = get_forward_ixs(
+        cache,
+        received,
+        &wallet_pubkey,
+        cex_pubkey,
+        account.get_cex_memo(),
+        account.get_split_rules().unwrap_or(&config.split_rules),
+        &payer_kp.pubkey(),
+    )
+    .await?;
+
+    let (_, fee) = send_ixs(
+        provider,
+        cache,
+        &instructions,
+        payer_kp,
+        signing_keypairs,
+        &wallet_pubkey,
+    )
+    .await?;
+
+    amounts.grass_forwarded = received;
+    amounts.fees_paid += fee;
+
+    account.set_forwarded_grass(true);
 
     Ok(())
 }
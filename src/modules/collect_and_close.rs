@@ -1,89 +1,136 @@
-use std::{str::FromStr, time::Duration};
+use std::str::FromStr;
 
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
-    instruction::Instruction,
-    native_token::{lamports_to_sol, sol_to_lamports},
-    program_pack::Pack,
-    pubkey::Pubkey,
-    signature::Keypair,
-    signer::Signer,
-    transaction::Transaction,
+    commitment_config::CommitmentConfig, instruction::Instruction, program_pack::Pack,
+    pubkey::Pubkey, signature::Keypair, signer::Signer, transaction::Transaction,
+};
+
+use super::{
+    prefetch::prefetch_accounts,
+    report::{get_tx_fee, Amounts, RunReport},
 };
 
 use crate::{
     config::Config,
-    db::{account::Account, database::Database},
+    db::{
+        account::Account,
+        database::Database,
+        failures::{FailureLog, Step},
+        order::AccountOrder,
+        selection::Selection,
+    },
     onchain::{
+        cache::AccountCache,
         constants::{GRASS_PUBKEY, TOKEN_PROGRAM_ID},
         derive::derive_ata,
-        ixs::Instructions,
+        fee_payer::FeePayerPool,
+        ixs::{get_close_fee, Instructions},
+        transport::RpcPool,
         tx::send_and_confirm_tx,
         typedefs::CreateAtaArgs,
     },
     utils::misc::pretty_sleep,
 };
 
-pub async fn collect_and_close(mut db: Database, config: &Config) -> eyre::Result<()> {
-    let provider = RpcClient::new_with_timeout_and_commitment(
-        config.solana_rpc_url.clone(),
-        Duration::from_secs(60),
-        CommitmentConfig::processed(),
-    );
+pub async fn collect_and_close(
+    mut db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let rpc = RpcPool::new(config, &db)?;
+    let provider = rpc.direct();
+
+    let mut fee_payers = FeePayerPool::from_config(config)?;
+    let mut order = AccountOrder::from_config(&db, config);
+    let mut failures = FailureLog::read(Step::CollectAndClose).await?;
+    let mut report = RunReport::new(Step::CollectAndClose);
+
+    let mut cache = AccountCache::new(provider, config);
+    prefetch_accounts(
+        &mut cache,
+        &rpc,
+        db.0.iter()
+            .filter(|a| selected.contains(a) && (!a.get_collected_sol() || !a.get_closed_ata())),
+        config,
+    )
+    .await?;
+
+    while let Some(account) = order.next(&mut db, |a| {
+        selected.contains(a) && (!a.get_collected_sol() || !a.get_closed_ata())
+    }) {
+        let fee_payer = match fee_payers.next(provider).await {
+            Ok(fee_payer) => fee_payer,
+            Err(e) => {
+                tracing::error!("{}", e);
+                break;
+            }
+        };
 
-    while let Some(account) =
-        db.get_random_account_with_filter(|a| !a.get_collected_sol() || !a.get_closed_ata())
-    {
-        if let Err(e) = process_account(&provider, account, config).await {
-            tracing::error!("{}", e);
-        } else {
-            account.set_closed_ata(true);
-            account.set_collected_sol(true);
-            db.update();
+        let account_provider = rpc.get(account.get_proxy());
+        cache.set_provider(account_provider);
+
+        match process_account(
+            account_provider,
+            &mut cache,
+            account,
+            fee_payer.as_ref(),
+            config,
+        )
+        .await
+        {
+            Ok(amounts) => {
+                report.processed(&account.get_pubkey(), amounts);
+                failures.resolve(&account.get_pubkey());
+                account.set_closed_ata(true);
+                account.set_collected_sol(true);
+                db.update();
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+                failures.record(&account.get_pubkey(), &e);
+                report.failed(&account.get_pubkey(), &e);
+            }
         };
 
         pretty_sleep(config.claim_sleep_range).await;
     }
 
-    Ok(())
+    report.finish()
 }
 
 async fn get_ixs(
-    provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
     wallet_pubkey: &Pubkey,
     collector_pubkey: &Pubkey,
     payer_pubkey: &Pubkey,
-) -> eyre::Result<Option<Vec<Instruction>>> {
+) -> eyre::Result<Option<(Vec<Instruction>, Amounts)>> {
     let mut ixs = vec![];
+    let mut amounts = Amounts::default();
 
     let (wallet_token_ata, _) = derive_ata(wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
-    let token_ata_exist = provider.get_account_data(&wallet_token_ata).await.is_ok();
+    let token_ata_exist = cache.exists(&wallet_token_ata).await?;
 
     let mut should_add_rent = false;
 
-    let rent = provider
-        .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
-        .await?;
+    let rent = cache.get_rent(spl_token::state::Account::LEN).await?;
 
     if token_ata_exist {
         if payer_pubkey == wallet_pubkey {
             should_add_rent = true;
         }
 
-        let token_account = provider
-            .get_token_account_balance(&wallet_token_ata)
-            .await?;
-
-        let token_account_balance = token_account.amount.parse::<u64>()?;
+        let token_account_balance = cache
+            .get_token_balance(&wallet_token_ata)
+            .await?
+            .unwrap_or(0);
 
         if token_account_balance != 0 {
             let (collector_token_ata, _) =
                 derive_ata(collector_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
-            let collector_token_ata_exist = provider
-                .get_account_data(&collector_token_ata)
-                .await
-                .is_ok();
+            let collector_token_ata_exist = cache.exists(&collector_token_ata).await?;
 
             if !collector_token_ata_exist {
                 let create_ata_args = CreateAtaArgs {
@@ -92,7 +139,7 @@ async fn get_ixs(
                     wallet_address: *collector_pubkey,
                     token_mint_address: GRASS_PUBKEY,
                     token_program_id: TOKEN_PROGRAM_ID,
-                    instruction: 0,
+                    instruction: 1,
                 };
 
                 ixs.push(Instructions::create_ata(create_ata_args));
@@ -108,18 +155,23 @@ async fn get_ixs(
                 token_account_balance,
                 9u8,
             )?);
+
+            amounts.grass_forwarded = token_account_balance;
         }
 
         let close_ix =
             Instructions::close_account(&wallet_token_ata, wallet_pubkey, payer_pubkey, rent);
 
         ixs.extend_from_slice(&close_ix);
+
+        amounts.rent_reclaimed = rent;
+        amounts.close_fees_paid = get_close_fee(rent);
     }
 
-    let mut balance = provider.get_balance(wallet_pubkey).await?;
+    let mut balance = cache.get_balance(wallet_pubkey).await?;
 
     balance = if should_add_rent {
-        balance + rent - sol_to_lamports(lamports_to_sol(rent) * 0.03)
+        balance + rent - get_close_fee(rent)
     } else {
         balance
     };
@@ -129,7 +181,7 @@ async fn get_ixs(
             "Wallet doesn't have enough SOL to withdraw: {} | 5001 at least",
             balance
         );
-        return Ok(Some(ixs));
+        return Ok(Some((ixs, amounts)));
     }
 
     let amount_to_withdraw = if payer_pubkey == wallet_pubkey {
@@ -144,41 +196,41 @@ async fn get_ixs(
         amount_to_withdraw,
     ));
 
-    Ok(Some(ixs))
+    amounts.sol_collected = amount_to_withdraw;
+
+    Ok(Some((ixs, amounts)))
 }
 
 async fn process_account(
     provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
     account: &mut Account,
+    fee_payer: Option<&Keypair>,
     config: &Config,
-) -> eyre::Result<()> {
+) -> eyre::Result<Option<Amounts>> {
     let wallet = account.keypair();
     let wallet_pubkey = account.get_pubkey();
     let collector_pubkey = Pubkey::from_str(&config.collector_pubkey)?;
 
     tracing::info!("Wallet address: `{}`", wallet.pubkey());
 
-    let payer_kp = match config.use_external_fee_pay {
-        true => Keypair::from_base58_string(&config.external_fee_payer_pk),
-        false => wallet.insecure_clone(),
+    let payer_kp = match fee_payer {
+        Some(fee_payer) => fee_payer.insecure_clone(),
+        None => wallet.insecure_clone(),
     };
 
-    let signing_keypairs = match config.use_external_fee_pay {
-        true => vec![&payer_kp, &wallet],
-        false => vec![&wallet],
+    let signing_keypairs = match fee_payer {
+        Some(_) => vec![&payer_kp, &wallet],
+        None => vec![&wallet],
     };
 
-    let instructions = match get_ixs(
-        provider,
-        &wallet_pubkey,
-        &collector_pubkey,
-        &payer_kp.pubkey(),
-    )
-    .await?
-    {
-        Some(ixs) => ixs,
-        None => return Ok(()),
-    };
+    let (instructions, mut amounts) =
+        match get_ixs(cache, &wallet_pubkey, &collector_pubkey, &payer_kp.pubkey()).await? {
+            Some(part) => part,
+            None => return Ok(None),
+        };
+
+    cache.invalidate(&instructions);
 
     let (recent_blockhash, _) = provider
         .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
@@ -191,7 +243,9 @@ async fn process_account(
         recent_blockhash,
     );
 
+    amounts.fees_paid = get_tx_fee(provider, &tx, &wallet_pubkey).await;
+
     send_and_confirm_tx(provider, tx, &recent_blockhash).await?;
 
-    Ok(())
+    Ok(Some(amounts))
 }
@@ -1,4 +1,4 @@
-use std::{str::FromStr, time::Duration};
+use std::str::FromStr;
 
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
@@ -6,43 +6,102 @@ use solana_sdk::{
     signature::Keypair, signer::Signer, transaction::Transaction,
 };
 
+use super::{
+    prefetch::prefetch_accounts,
+    report::{get_tx_fee, Amounts, RunReport},
+};
+
 use crate::{
     config::Config,
-    db::{account::Account, database::Database},
-    onchain::tx::send_and_confirm_tx,
+    db::{
+        account::Account,
+        database::Database,
+        failures::{FailureLog, Step},
+        order::AccountOrder,
+        selection::Selection,
+    },
+    onchain::{
+        cache::AccountCache, fee_payer::FeePayerPool, transport::RpcPool, tx::send_and_confirm_tx,
+    },
     utils::misc::pretty_sleep,
 };
 
-pub async fn collect_sol(mut db: Database, config: &Config) -> eyre::Result<()> {
-    let provider = RpcClient::new_with_timeout_and_commitment(
-        config.solana_rpc_url.clone(),
-        Duration::from_secs(60),
-        CommitmentConfig::processed(),
-    );
+pub async fn collect_sol(
+    mut db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let rpc = RpcPool::new(config, &db)?;
+    let provider = rpc.direct();
+
+    let mut fee_payers = FeePayerPool::from_config(config)?;
+    let mut order = AccountOrder::from_config(&db, config);
+    let mut failures = FailureLog::read(Step::Collect).await?;
+    let mut report = RunReport::new(Step::Collect);
+
+    let mut cache = AccountCache::new(provider, config);
+    prefetch_accounts(
+        &mut cache,
+        &rpc,
+        db.0.iter()
+            .filter(|a| selected.contains(a) && !a.get_collected_sol()),
+        config,
+    )
+    .await?;
 
-    while let Some(account) = db.get_random_account_with_filter(|a| !a.get_collected_sol()) {
-        if let Err(e) = process_account(&provider, account, config).await {
-            tracing::error!("{}", e);
-        } else {
-            account.set_collected_sol(true);
-            db.update();
+    while let Some(account) =
+        order.next(&mut db, |a| selected.contains(a) && !a.get_collected_sol())
+    {
+        let fee_payer = match fee_payers.next(provider).await {
+            Ok(fee_payer) => fee_payer,
+            Err(e) => {
+                tracing::error!("{}", e);
+                break;
+            }
+        };
+
+        let account_provider = rpc.get(account.get_proxy());
+        cache.set_provider(account_provider);
+
+        match process_account(
+            account_provider,
+            &mut cache,
+            account,
+            fee_payer.as_ref(),
+            config,
+        )
+        .await
+        {
+            Ok(amounts) => {
+                report.processed(&account.get_pubkey(), amounts);
+                failures.resolve(&account.get_pubkey());
+                account.set_collected_sol(true);
+                db.update();
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+                failures.record(&account.get_pubkey(), &e);
+                report.failed(&account.get_pubkey(), &e);
+            }
         };
 
         pretty_sleep(config.claim_sleep_range).await;
     }
 
-    Ok(())
+    report.finish()
 }
 
 async fn get_ixs(
-    provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
     wallet_pubkey: &Pubkey,
     collector_pubkey: &Pubkey,
     payer_pubkey: &Pubkey,
-) -> eyre::Result<Option<Vec<Instruction>>> {
+) -> eyre::Result<Option<(Vec<Instruction>, Amounts)>> {
     let mut ixs = vec![];
 
-    let balance = provider.get_balance(wallet_pubkey).await?;
+    let balance = cache.get_balance(wallet_pubkey).await?;
 
     if balance <= 5000 {
         tracing::warn!(
@@ -64,41 +123,44 @@ async fn get_ixs(
         amount_to_withdraw,
     ));
 
-    Ok(Some(ixs))
+    let amounts = Amounts {
+        sol_collected: amount_to_withdraw,
+        ..Default::default()
+    };
+
+    Ok(Some((ixs, amounts)))
 }
 
 async fn process_account(
     provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
     account: &mut Account,
+    fee_payer: Option<&Keypair>,
     config: &Config,
-) -> eyre::Result<()> {
+) -> eyre::Result<Option<Amounts>> {
     let wallet = account.keypair();
     let wallet_pubkey = account.get_pubkey();
     let collector_pubkey = Pubkey::from_str(&config.collector_pubkey)?;
 
     tracing::info!("Wallet address: `{}`", wallet.pubkey());
 
-    let payer_kp = match config.use_external_fee_pay {
-        true => Keypair::from_base58_string(&config.external_fee_payer_pk),
-        false => wallet.insecure_clone(),
+    let payer_kp = match fee_payer {
+        Some(fee_payer) => fee_payer.insecure_clone(),
+        None => wallet.insecure_clone(),
     };
 
-    let signing_keypairs = match config.use_external_fee_pay {
-        true => vec![&payer_kp, &wallet],
-        false => vec![&wallet],
+    let signing_keypairs = match fee_payer {
+        Some(_) => vec![&payer_kp, &wallet],
+        None => vec![&wallet],
     };
 
-    let instructions = match get_ixs(
-        provider,
-        &wallet_pubkey,
-        &collector_pubkey,
-        &payer_kp.pubkey(),
-    )
-    .await?
-    {
-        Some(ixs) => ixs,
-        None => return Ok(()),
-    };
+    let (instructions, mut amounts) =
+        match get_ixs(cache, &wallet_pubkey, &collector_pubkey, &payer_kp.pubkey()).await? {
+            Some(part) => part,
+            None => return Ok(None),
+        };
+
+    cache.invalidate(&instructions);
 
     let (recent_blockhash, _) = provider
         .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
@@ -111,7 +173,9 @@ async fn process_account(
         recent_blockhash,
     );
 
+    amounts.fees_paid = get_tx_fee(provider, &tx, &wallet_pubkey).await;
+
     send_and_confirm_tx(provider, tx, &recent_blockhash).await?;
 
-    Ok(())
+    Ok(Some(amounts))
 }
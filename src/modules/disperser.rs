@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+
+use super::{
+    planner::{plan_wallet, CostPlan, Prices},
+    prefetch::prefetch_accounts,
+    report::{Amounts, RunReport},
+};
+
+use crate::{
+    config::{Config, DisperseMode},
+    db::{
+        account::Account,
+        database::Database,
+        failures::{FailureLog, Step},
+        order::AccountOrder,
+        selection::Selection,
+    },
+    onchain::{cache::AccountCache, transport::RpcPool, tx::send_and_confirm_tx},
+    utils::misc::{pretty_sleep, random_in_range},
+};
+
+pub async fn disperse_sol(
+    mut db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let rpc = RpcPool::new(config, &db)?;
+    let provider = rpc.direct();
+
+    let funder_kp = Keypair::from_base58_string(&config.funder_pk);
+
+    tracing::info!("Funder address: `{}`", funder_kp.pubkey());
+
+    let pending = AccountOrder::from_config(&db, config)
+        .indices()
+        .iter()
+        .copied()
+        .filter(|&index| selected.contains(&db.0[index]) && !db.0[index].get_funded())
+        .collect::<Vec<_>>();
+
+    let mut failures = FailureLog::read(Step::Disperse).await?;
+    let mut report = RunReport::new(Step::Disperse);
+
+    let mut cache = AccountCache::new(provider, config);
+
+    if config.disperse_mode == DisperseMode::Required {
+        prefetch_accounts(
+            &mut cache,
+            &rpc,
+            db.0.iter()
+                .filter(|a| selected.contains(a) && !a.get_funded()),
+            config,
+        )
+        .await?;
+    }
+
+    let prices = Prices::fetch(&mut cache, config).await?;
+
+    for batch in pending.chunks(config.disperse_batch_size.max(1)) {
+        let mut transfers = Vec::with_capacity(batch.len());
+
+        for &index in batch {
+            let account = &db.0[index];
+            let wallet_pubkey = account.get_pubkey();
+
+            match get_top_up_amount(&mut cache, &prices, account, config).await {
+                Ok(amount) => transfers.push((index, wallet_pubkey, amount)),
+                Err(e) => {
+                    tracing::error!("Failed to compute top-up for `{wallet_pubkey}`: {e}");
+                    failures.record(&wallet_pubkey, &e);
+                    report.failed(&wallet_pubkey, &e);
+                }
+            }
+        }
+
+        match process_batch(provider, &funder_kp, &transfers).await {
+            Ok(signature) => {
+                let signature = signature.map(|signature| signature.to_string());
+
+                for (index, wallet_pubkey, amount) in transfers {
+                    failures.resolve(&wallet_pubkey);
+                    report.processed(&wallet_pubkey, (amount > 0).then(Amounts::default));
+
+                    // wallets that got nothing are checked again on the next run
+                    if amount > 0 {
+                        db.0[index].set_funded(amount, signature.clone());
+                        db.0[index].set_collected_sol(false);
+                    }
+                }
+
+                db.update();
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+
+                for (_, wallet_pubkey, _) in &transfers {
+                    failures.record(wallet_pubkey, &e);
+                    report.failed(wallet_pubkey, &e);
+                }
+            }
+        }
+
+        pretty_sleep(config.claim_sleep_range).await;
+    }
+
+    report.finish()
+}
+
+/// Lamports the wallet misses to pay for its remaining operations, as the cost plan counts them.
+async fn get_required_lamports(
+    cache: &mut AccountCache<'_>,
+    prices: &Prices,
+    account: &Account,
+    config: &Config,
+) -> eyre::Result<u64> {
+    // every wallet is funded for the destination ATAs it may have to create itself
+    let cost = plan_wallet(
+        cache,
+        prices,
+        account,
+        &mut HashSet::new(),
+        &mut CostPlan::default(),
+        config,
+    )
+    .await?;
+
+    Ok(cost.wallet.saturating_sub(cost.wallet_balance))
+}
+
+async fn get_top_up_amount(
+    cache: &mut AccountCache<'_>,
+    prices: &Prices,
+    account: &Account,
+    config: &Config,
+) -> eyre::Result<u64> {
+    let amount = match config.disperse_mode {
+        DisperseMode::Fixed => sol_to_lamports(config.disperse_amount),
+        DisperseMode::Range => sol_to_lamports(random_in_range(config.disperse_amount_range)),
+        DisperseMode::Required => get_required_lamports(cache, prices, account, config).await?,
+    };
+
+    Ok(amount)
+}
+
+async fn process_batch(
+    provider: &RpcClient,
+    funder_kp: &Keypair,
+    transfers: &[(usize, Pubkey, u64)],
+) -> eyre::Result<Option<Signature>> {
+    let instructions = transfers
+        .iter()
+        .filter(|(_, _, amount)| *amount > 0)
+        .map(|(_, wallet_pubkey, amount)| {
+            tracing::info!(
+                "Funding `{}` with {} SOL",
+                wallet_pubkey,
+                lamports_to_sol(*amount)
+            );
+
+            solana_sdk::system_instruction::transfer(&funder_kp.pubkey(), wallet_pubkey, *amount)
+        })
+        .collect::<Vec<_>>();
+
+    if instructions.is_empty() {
+        tracing::info!("Every wallet in the batch already has enough SOL");
+        return Ok(None);
+    }
+
+    let (recent_blockhash, _) = provider
+        .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+        .await?;
+
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&funder_kp.pubkey()),
+        &[funder_kp],
+        recent_blockhash,
+    );
+
+    let signature = send_and_confirm_tx(provider, tx, &recent_blockhash).await?;
+
+    Ok(Some(signature))
+}
@@ -0,0 +1,435 @@
+use std::{collections::HashSet, str::FromStr};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, instruction::Instruction, pubkey::Pubkey,
+    signature::Keypair, signer::Signer, transaction::Transaction,
+};
+
+use super::{
+    prefetch::prefetch_accounts,
+    report::{get_tx_fee, Amounts, RunReport},
+};
+
+use crate::{
+    config::{validate_split_rules, Config, MemoFormat, SplitRule},
+    db::{
+        account::Account,
+        database::Database,
+        failures::{FailureLog, Step},
+        order::AccountOrder,
+        selection::Selection,
+    },
+    onchain::{
+        cache::AccountCache,
+        constants::{GRASS_PUBKEY, TOKEN_PROGRAM_ID},
+        derive::derive_ata,
+        fee_payer::FeePayerPool,
+        ixs::Instructions,
+        transport::RpcPool,
+        tx::send_and_confirm_tx,
+        typedefs::CreateAtaArgs,
+    },
+    utils::misc::pretty_sleep,
+};
+
+pub async fn forward_grass(
+    mut db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let rpc = RpcPool::new(config, &db)?;
+    let provider = rpc.direct();
+
+    let mut fee_payers = FeePayerPool::from_config(config)?;
+    let mut skipped = HashSet::new();
+    let mut order = AccountOrder::from_config(&db, config);
+    let mut failures = FailureLog::read(Step::Forward).await?;
+    let mut report = RunReport::new(Step::Forward);
+
+    let mut cache = AccountCache::new(provider, config);
+    prefetch_accounts(
+        &mut cache,
+        &rpc,
+        db.0.iter()
+            .filter(|a| selected.contains(a) && !a.get_forwarded_grass()),
+        config,
+    )
+    .await?;
+
+    while let Some(account) = order.next(&mut db, |a| {
+        selected.contains(a) && !a.get_forwarded_grass() && !skipped.contains(&a.get_pubkey())
+    }) {
+        let fee_payer = match fee_payers.next(provider).await {
+            Ok(fee_payer) => fee_payer,
+            Err(e) => {
+                tracing::error!("{}", e);
+                break;
+            }
+        };
+
+        let account_provider = rpc.get(account.get_proxy());
+        cache.set_provider(account_provider);
+
+        match process_account(
+            account_provider,
+            &mut cache,
+            account,
+            fee_payer.as_ref(),
+            config,
+        )
+        .await
+        {
+            Ok(Some(amounts)) => {
+                report.processed(&account.get_pubkey(), Some(amounts));
+                failures.resolve(&account.get_pubkey());
+                account.set_forwarded_grass(true);
+                db.update();
+            }
+            Ok(None) => {
+                report.processed(&account.get_pubkey(), None);
+                failures.resolve(&account.get_pubkey());
+                skipped.insert(account.get_pubkey());
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+                failures.record(&account.get_pubkey(), &e);
+                report.failed(&account.get_pubkey(), &e);
+            }
+        };
+
+        pretty_sleep(config.claim_sleep_range).await;
+    }
+
+    report.finish()
+}
+
+pub(super) fn validate_cex_memo(memo: Option<&str>, config: &Config) -> eyre::Result<()> {
+    let memo = match memo {
+        Some(memo) => memo,
+        None if config.cex_memo_required => {
+            eyre::bail!("CEX deposit memo is required, but none is set for this account")
+        }
+        None => return Ok(()),
+    };
+
+    if memo.is_empty() || memo.len() > 256 {
+        eyre::bail!("CEX deposit memo must be 1 to 256 bytes long: `{memo}`");
+    }
+
+    let is_valid = match config.cex_memo_format {
+        MemoFormat::Any => memo.chars().all(|c| !c.is_control()),
+        MemoFormat::Numeric => memo.chars().all(|c| c.is_ascii_digit()),
+        MemoFormat::Alphanumeric => memo.chars().all(|c| c.is_ascii_alphanumeric()),
+    };
+
+    if !is_valid {
+        eyre::bail!("CEX deposit memo `{memo}` doesn't match the configured format");
+    }
+
+    Ok(())
+}
+
+pub(super) fn get_destinations(account: &Account, config: &Config) -> eyre::Result<Vec<Pubkey>> {
+    let split_rules = account.get_split_rules().unwrap_or(&config.split_rules);
+
+    let mut destinations = split_rules
+        .iter()
+        .map(|rule| Pubkey::from_str(&rule.destination))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    destinations.push(Pubkey::from_str(account.get_cex_address())?);
+
+    Ok(destinations)
+}
+
+/// Splits `amount` (in base units) between the split rule destinations and the CEX address.
+/// Shares are floored, so the CEX address always receives the exact remainder.
+pub(super) fn split_amount(
+    amount: u64,
+    cex_pubkey: &Pubkey,
+    split_rules: &[SplitRule],
+) -> eyre::Result<Vec<(Pubkey, u64)>> {
+    validate_split_rules(split_rules)?;
+
+    let mut remainder = amount;
+    let mut shares = Vec::with_capacity(split_rules.len() + 1);
+
+    for rule in split_rules {
+        let destination = Pubkey::from_str(&rule.destination)?;
+        let bps = (rule.percent * 100.0).round() as u128;
+        let min_amount = (rule.min_amount * 10f64.powi(9)).round() as u64;
+
+        let share = ((amount as u128 * bps) / 10_000) as u64;
+        let share = share.max(min_amount).min(remainder);
+
+        remainder -= share;
+        shares.push((destination, share));
+    }
+
+    shares.push((*cex_pubkey, remainder));
+
+    Ok(shares)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn get_forward_ixs(
+    cache: &mut AccountCache<'_>,
+    amount: u64,
+    wallet_pubkey: &Pubkey,
+    cex_pubkey: &Pubkey,
+    cex_memo: Option<&str>,
+    split_rules: &[SplitRule],
+    payer_pubkey: &Pubkey,
+) -> eyre::Result<Vec<Instruction>> {
+    let mut ixs = vec![];
+    let mut checked_atas = HashSet::new();
+
+    let (wallet_token_ata, _) = derive_ata(wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
+
+    for (destination, share) in split_amount(amount, cex_pubkey, split_rules)? {
+        if share == 0 {
+            continue;
+        }
+
+        let (destination_token_ata, _) = derive_ata(&destination, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
+
+        if checked_atas.insert(destination_token_ata)
+            && !cache.exists(&destination_token_ata).await?
+        {
+            let create_ata_args = CreateAtaArgs {
+                funding_address: *payer_pubkey,
+                associated_account_address: destination_token_ata,
+                wallet_address: destination,
+                token_mint_address: GRASS_PUBKEY,
+                token_program_id: TOKEN_PROGRAM_ID,
+                instruction: 1,
+            };
+
+            ixs.push(Instructions::create_ata(create_ata_args));
+        }
+
+        tracing::info!(
+            "Sending {} GRASS to `{}`",
+            share as f64 / 10f64.powi(9),
+            destination
+        );
+
+        let transfer_ix = spl_token::instruction::transfer_checked(
+            &TOKEN_PROGRAM_ID,
+            &wallet_token_ata,
+            &GRASS_PUBKEY,
+            &destination_token_ata,
+            wallet_pubkey,
+            &[wallet_pubkey],
+            share,
+            9u8,
+        )?;
+
+        ixs.push(transfer_ix);
+    }
+
+    if let Some(memo) = cex_memo {
+        ixs.push(Instructions::memo(memo, wallet_pubkey));
+    }
+
+    Ok(ixs)
+}
+
+async fn get_ixs(
+    cache: &mut AccountCache<'_>,
+    wallet_pubkey: &Pubkey,
+    cex_pubkey: &Pubkey,
+    cex_memo: Option<&str>,
+    split_rules: &[SplitRule],
+    payer_pubkey: &Pubkey,
+) -> eyre::Result<Option<(Vec<Instruction>, Amounts)>> {
+    let (wallet_token_ata, _) = derive_ata(wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
+    let token_account_balance = match cache.get_token_balance(&wallet_token_ata).await? {
+        Some(balance) => balance,
+        None => {
+            tracing::warn!("Grass ATA already closed or not exist");
+            return Ok(None);
+        }
+    };
+
+    if token_account_balance == 0 {
+        tracing::warn!("Grass token account balance is 0, nothing to forward");
+        return Ok(None);
+    }
+
+    tracing::info!(
+        "Forwarding {} GRASS to `{}`",
+        token_account_balance as f64 / 10f64.powi(9),
+        cex_pubkey
+    );
+
+    let ixs = get_forward_ixs(
+        cache,
+        token_account_balance,
+        wallet_pubkey,
+        cex_pubkey,
+        cex_memo,
+        split_rules,
+        payer_pubkey,
+    )
+    .await?;
+
+    let amounts = Amounts {
+        grass_forwarded: token_account_balance,
+        ..Default::default()
+    };
+
+    Ok(Some((ixs, amounts)))
+}
+
+/// Returns what was forwarded, or `None` for wallets without a balance, which keep their status.
+async fn process_account(
+    provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
+    account: &mut Account,
+    fee_payer: Option<&Keypair>,
+    config: &Config,
+) -> eyre::Result<Option<Amounts>> {
+    let wallet = account.keypair();
+    let wallet_pubkey = account.get_pubkey();
+    let cex_pubkey = Pubkey::from_str(account.get_cex_address())?;
+    let cex_memo = account.get_cex_memo();
+    let split_rules = account.get_split_rules().unwrap_or(&config.split_rules);
+
+    tracing::info!("Wallet address: `{}`", wallet.pubkey());
+
+    validate_cex_memo(cex_memo, config)?;
+
+    let payer_kp = match fee_payer {
+        Some(fee_payer) => fee_payer.insecure_clone(),
+        None => wallet.insecure_clone(),
+    };
+
+    let signing_keypairs = match fee_payer {
+        Some(_) => vec![&payer_kp, &wallet],
+        None => vec![&wallet],
+    };
+
+    let (instructions, mut amounts) = match get_ixs(
+        cache,
+        &wallet_pubkey,
+        &cex_pubkey,
+        cex_memo,
+        split_rules,
+        &payer_kp.pubkey(),
+    )
+    .await?
+    {
+        Some(part) => part,
+        None => return Ok(None),
+    };
+
+    cache.invalidate(&instructions);
+
+    let (recent_blockhash, _) = provider
+        .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+        .await?;
+
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer_kp.pubkey()),
+        &signing_keypairs,
+        recent_blockhash,
+    );
+
+    amounts.fees_paid = get_tx_fee(provider, &tx, &wallet_pubkey).await;
+
+    send_and_confirm_tx(provider, tx, &recent_blockhash).await?;
+
+    Ok(Some(amounts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRASS: u64 = 1_000_000_000;
+
+    fn rule(destination: &Pubkey, percent: f64, min_amount: f64) -> SplitRule {
+        SplitRule {
+            destination: destination.to_string(),
+            percent,
+            min_amount,
+        }
+    }
+
+    #[test]
+    fn split_amount_floors_shares_and_sends_remainder_to_cex() {
+        let cex = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let exchange = Pubkey::new_unique();
+
+        let shares = split_amount(
+            1_001,
+            &cex,
+            &[rule(&treasury, 33.33, 0.0), rule(&exchange, 33.33, 0.0)],
+        )
+        .unwrap();
+
+        assert_eq!(shares, vec![(treasury, 333), (exchange, 333), (cex, 335)]);
+        assert_eq!(shares.iter().map(|(_, share)| share).sum::<u64>(), 1_001);
+    }
+
+    #[test]
+    fn split_amount_without_rules_sends_everything_to_cex() {
+        let cex = Pubkey::new_unique();
+
+        assert_eq!(split_amount(42, &cex, &[]).unwrap(), vec![(cex, 42)]);
+    }
+
+    #[test]
+    fn split_amount_raises_share_to_min_amount() {
+        let cex = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+
+        let shares = split_amount(10 * GRASS, &cex, &[rule(&treasury, 10.0, 2.5)]).unwrap();
+
+        assert_eq!(
+            shares,
+            vec![(treasury, 2_500_000_000), (cex, 7_500_000_000)]
+        );
+    }
+
+    #[test]
+    fn split_amount_caps_min_amount_at_available_amount() {
+        let cex = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let exchange = Pubkey::new_unique();
+
+        let shares = split_amount(
+            GRASS,
+            &cex,
+            &[rule(&treasury, 10.0, 0.8), rule(&exchange, 10.0, 0.5)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            shares,
+            vec![(treasury, 800_000_000), (exchange, 200_000_000), (cex, 0)]
+        );
+    }
+
+    #[test]
+    fn split_amount_rejects_invalid_rules() {
+        let cex = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+
+        for rules in [
+            vec![rule(&treasury, -1.0, 0.0)],
+            vec![rule(&treasury, f64::NAN, 0.0)],
+            vec![rule(&treasury, 100.5, 0.0)],
+            vec![rule(&treasury, 60.0, 0.0), rule(&cex, 50.0, 0.0)],
+            vec![rule(&treasury, 10.0, -1.0)],
+        ] {
+            assert!(split_amount(GRASS, &cex, &rules).is_err());
+        }
+    }
+}
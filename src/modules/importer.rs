@@ -0,0 +1,387 @@
+use std::{path::Path, str::FromStr};
+
+use serde::Deserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer, transaction::Transaction};
+
+use super::{
+    claimer::{get_ixs as get_claim_ixs, get_sent_allocation},
+    prefetch::prefetch_accounts,
+};
+
+use crate::{
+    config::Config,
+    db::{database::Database, selection::Selection},
+    getgrass::{
+        cache::{CachedReceipt, ReceiptCache},
+        typedefs::Cluster,
+    },
+    onchain::{
+        cache::AccountCache,
+        derive::{derive_claim_status, derive_merkle_distributor},
+        fee_payer::FeePayerPool,
+        transport::RpcPool,
+        tx::simulate_tx,
+    },
+    utils::files::read_file_lines,
+};
+
+#[derive(Deserialize)]
+struct ProofRecord {
+    #[serde(alias = "walletAddress")]
+    wallet: String,
+    #[serde(alias = "versionNumber")]
+    version: u32,
+    allocation: u64,
+    proof: Vec<ProofNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProofNode {
+    Bytes([u8; 32]),
+    Hex(String),
+}
+
+impl ProofNode {
+    fn to_bytes(&self) -> eyre::Result<[u8; 32]> {
+        match self {
+            ProofNode::Bytes(bytes) => Ok(*bytes),
+            ProofNode::Hex(hex) => decode_hex_node(hex),
+        }
+    }
+}
+
+fn decode_hex_node(hex: &str) -> eyre::Result<[u8; 32]> {
+    let hex = hex.trim().trim_start_matches("0x");
+
+    if hex.len() != 64 || !hex.is_ascii() {
+        eyre::bail!("Proof node must be 32 bytes of hex: `{hex}`");
+    }
+
+    let mut node = [0u8; 32];
+
+    for (i, byte) in node.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| eyre::eyre!("Proof node must be 32 bytes of hex: `{hex}`"))?;
+    }
+
+    Ok(node)
+}
+
+fn to_cached_receipt(record: ProofRecord, raw: serde_json::Value) -> eyre::Result<CachedReceipt> {
+    let proof = record
+        .proof
+        .iter()
+        .map(ProofNode::to_bytes)
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    Ok(CachedReceipt {
+        raw,
+        version_number: record.version,
+        proof,
+        allocation: record.allocation,
+    })
+}
+
+/// Reads a JSON array of proof records or a receipts cache file written by an earlier run.
+async fn read_json_proofs(path: &str) -> eyre::Result<Vec<(String, CachedReceipt)>> {
+    let contents = tokio::fs::read_to_string(path).await?;
+    let value = serde_json::from_str::<serde_json::Value>(&contents)?;
+
+    let records = match value {
+        serde_json::Value::Array(records) => records,
+        serde_json::Value::Object(_) => {
+            let receipts = serde_json::from_value::<ReceiptCache>(value)?;
+            return Ok(receipts.into_cluster_entries(Cluster::Mainnet));
+        }
+        _ => eyre::bail!("Proofs file must hold an array of records or a receipts cache"),
+    };
+
+    let mut entries = Vec::with_capacity(records.len());
+
+    for (index, raw) in records.into_iter().enumerate() {
+        let parsed = serde_json::from_value::<ProofRecord>(raw.clone())
+            .map_err(eyre::Report::from)
+            .and_then(|record| {
+                let wallet = record.wallet.clone();
+                Ok((wallet, to_cached_receipt(record, raw)?))
+            });
+
+        match parsed {
+            Ok(entry) => entries.push(entry),
+            Err(e) => tracing::error!("Record {}: {}", index + 1, e),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Reads `wallet,version,allocation,proof` lines, with proof nodes in hex separated by `;`.
+async fn read_csv_proofs(path: &str) -> eyre::Result<Vec<(String, CachedReceipt)>> {
+    let lines = read_file_lines(path).await?;
+    let mut entries = Vec::with_capacity(lines.len());
+
+    for (index, line) in lines.iter().enumerate() {
+        if index == 0 && line.to_lowercase().starts_with("wallet") {
+            continue;
+        }
+
+        let parsed = parse_csv_line(line);
+
+        match parsed {
+            Ok(entry) => entries.push(entry),
+            Err(e) => tracing::error!("Line {}: {}", index + 1, e),
+        }
+    }
+
+    Ok(entries)
+}
+
+fn parse_csv_line(line: &str) -> eyre::Result<(String, CachedReceipt)> {
+    let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+
+    let [wallet, version, allocation, proof] = fields[..] else {
+        eyre::bail!("Expected `wallet,version,allocation,proof`, got `{line}`");
+    };
+
+    let record = ProofRecord {
+        wallet: wallet.to_string(),
+        version: version.parse()?,
+        allocation: allocation.parse()?,
+        proof: proof
+            .split(';')
+            .filter(|node| !node.trim().is_empty())
+            .map(|node| ProofNode::Hex(node.to_string()))
+            .collect(),
+    };
+
+    let raw = serde_json::json!({
+        "wallet": wallet,
+        "version": record.version,
+        "allocation": record.allocation,
+        "proof": proof,
+    });
+
+    Ok((wallet.to_string(), to_cached_receipt(record, raw)?))
+}
+
+/// Checks the proof against the chain by simulating the claim. Wallets that already received
+/// their full allocation are accepted without a simulation, any other proof that can't be
+/// simulated is rejected.
+async fn validate_receipt(
+    provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
+    wallet_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    receipt: &CachedReceipt,
+) -> eyre::Result<()> {
+    let (merkle_distributor_pubkey, _) = derive_merkle_distributor(receipt.version_number);
+    let (claim_status_pubkey, _) = derive_claim_status(wallet_pubkey, &merkle_distributor_pubkey);
+
+    if let Some((total_allocation, sent_allocation)) =
+        get_sent_allocation(cache, &claim_status_pubkey).await?
+    {
+        if total_allocation != receipt.allocation {
+            eyre::bail!(
+                "Allocation {} doesn't match the on-chain allocation {}",
+                receipt.allocation,
+                total_allocation
+            );
+        }
+
+        if sent_allocation >= total_allocation {
+            return Ok(());
+        }
+    }
+
+    let Some(ixs) = get_claim_ixs(
+        cache,
+        receipt.version_number,
+        receipt.proof.clone(),
+        receipt.allocation,
+        wallet_pubkey,
+        payer_pubkey,
+    )
+    .await?
+    else {
+        eyre::bail!(
+            "Claim can't be simulated: the wallet is unfunded and no fee payer or funder is set"
+        );
+    };
+
+    let tx = Transaction::new_with_payer(&ixs, Some(payer_pubkey));
+
+    simulate_tx(provider, &tx).await
+}
+
+pub async fn import_proofs(
+    db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let rpc = RpcPool::new(config, &db)?;
+    let provider = rpc.direct();
+
+    let path = config.proofs_file_path.as_str();
+
+    let entries = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => read_csv_proofs(path).await?,
+        _ => read_json_proofs(path).await?,
+    };
+
+    tracing::info!("Read {} proofs from `{}`", entries.len(), path);
+
+    // claims of unfunded wallets are simulated with a fee payer or the funder covering the rent
+    let funded_payer_pubkey = FeePayerPool::from_config(config)?
+        .pubkeys()
+        .first()
+        .copied()
+        .or_else(|| {
+            (!config.funder_pk.is_empty())
+                .then(|| Keypair::from_base58_string(&config.funder_pk).pubkey())
+        });
+    let mut receipts = ReceiptCache::read().await?;
+
+    let mut cache = AccountCache::new(provider, config);
+    prefetch_accounts(
+        &mut cache,
+        &rpc,
+        db.0.iter().filter(|a| selected.contains(a)),
+        config,
+    )
+    .await?;
+
+    let mut imported = 0;
+
+    for (wallet_address, receipt) in entries {
+        let wallet_pubkey = match Pubkey::from_str(&wallet_address) {
+            Ok(pubkey) => pubkey,
+            Err(e) => {
+                tracing::error!("Invalid wallet address `{}`: {}", wallet_address, e);
+                continue;
+            }
+        };
+
+        let Some(account) = db.0.iter().find(|a| a.get_pubkey() == wallet_pubkey) else {
+            tracing::warn!(
+                "Wallet `{}` is not in the database, skipping",
+                wallet_address
+            );
+            continue;
+        };
+
+        if !selected.contains(account) {
+            continue;
+        }
+
+        let payer_pubkey = funded_payer_pubkey.unwrap_or(wallet_pubkey);
+
+        if receipt.version_number != config.claim_version {
+            tracing::error!(
+                "Proof of `{}` is rejected: it's for distributor version {}, but version {} is targeted",
+                wallet_address,
+                receipt.version_number,
+                config.claim_version
+            );
+            continue;
+        }
+
+        if let Err(e) = validate_receipt(
+            provider,
+            &mut cache,
+            &wallet_pubkey,
+            &payer_pubkey,
+            &receipt,
+        )
+        .await
+        {
+            tracing::error!("Proof of `{}` is rejected: {}", wallet_address, e);
+            continue;
+        }
+
+        receipts.insert(&wallet_address, Cluster::Mainnet, receipt);
+        imported += 1;
+    }
+
+    receipts.update();
+
+    tracing::info!("Proofs imported: {}", imported);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_hex(node: &[u8; 32]) -> String {
+        node.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn csv_line_round_trips_proof_nodes() {
+        let wallet = Pubkey::new_unique().to_string();
+        let nodes = [[0u8; 32], [0xab; 32], std::array::from_fn(|i| i as u8)];
+        let proof = nodes.iter().map(to_hex).collect::<Vec<_>>().join(";");
+
+        let (parsed_wallet, receipt) =
+            parse_csv_line(&format!("{wallet}, 2, 12500000000, {proof}")).unwrap();
+
+        assert_eq!(parsed_wallet, wallet);
+        assert_eq!(receipt.version_number, 2);
+        assert_eq!(receipt.allocation, 12_500_000_000);
+        assert_eq!(receipt.proof, nodes.to_vec());
+        assert_eq!(receipt.raw["proof"], proof);
+    }
+
+    #[test]
+    fn csv_line_skips_empty_proof_nodes() {
+        let node = [0x11; 32];
+        let line = format!("wallet,1,5,;{};", to_hex(&node));
+
+        let (_, receipt) = parse_csv_line(&line).unwrap();
+
+        assert_eq!(receipt.proof, vec![node]);
+    }
+
+    #[test]
+    fn csv_line_rejects_malformed_rows() {
+        let node = to_hex(&[0x11; 32]);
+
+        for line in [
+            String::new(),
+            "wallet,1,5".to_string(),
+            format!("wallet,1,5,{node},extra"),
+            format!("wallet,v1,5,{node}"),
+            format!("wallet,1,-5,{node}"),
+            format!("wallet,1,5,{}", &node[2..]),
+            format!("wallet,1,5,{node};zz"),
+        ] {
+            assert!(parse_csv_line(&line).is_err(), "`{line}` was accepted");
+        }
+    }
+
+    #[test]
+    fn hex_node_accepts_prefix_and_case() {
+        let node = [0xab; 32];
+        let hex = to_hex(&node);
+
+        assert_eq!(decode_hex_node(&hex).unwrap(), node);
+        assert_eq!(decode_hex_node(&format!(" 0x{hex} ")).unwrap(), node);
+        assert_eq!(decode_hex_node(&hex.to_uppercase()).unwrap(), node);
+    }
+
+    #[test]
+    fn hex_node_rejects_wrong_length_and_non_hex() {
+        let hex = to_hex(&[0xab; 32]);
+
+        assert!(decode_hex_node("").is_err());
+        assert!(decode_hex_node(&hex[..62]).is_err());
+        assert!(decode_hex_node(&format!("{hex}ab")).is_err());
+        assert!(decode_hex_node(&format!("{}zz", &hex[..62])).is_err());
+        assert!(decode_hex_node(&format!("{}é", &hex[..62])).is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use std::str::FromStr;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    packet::PACKET_DATA_SIZE,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::Transaction,
+};
+
+use super::{
+    claimer::{get_ixs as get_claim_ixs, get_sent_allocation, read_sent_allocation},
+    forwarder::{get_forward_ixs, validate_cex_memo},
+    prefetch::prefetch_leased_accounts,
+    receipts::{get_claim_receipt, get_offline_skipped},
+    report::{get_tx_fee, Amounts, RunReport},
+};
+
+use crate::{
+    config::Config,
+    db::{
+        account::Account,
+        database::Database,
+        failures::{FailureLog, Step},
+        order::AccountOrder,
+        selection::Selection,
+    },
+    getgrass::cache::{CachedReceipt, ReceiptCache},
+    onchain::{
+        cache::AccountCache,
+        constants::{ASSOCIATED_TOKEN_PROGRAM_ID, GRASS_PUBKEY, TOKEN_PROGRAM_ID},
+        derive::{derive_ata, derive_claim_status, derive_merkle_distributor},
+        fee_payer::FeePayerPool,
+        ixs::{get_close_fee, Instructions},
+        state::CLAIM_STATUS_LEN,
+        transport::RpcPool,
+        tx::{send_and_confirm_tx, simulate_tx_accounts},
+    },
+    utils::{
+        misc::pretty_sleep,
+        proxy_pool::{ProxyLease, ProxyPool},
+    },
+};
+
+const TX_FEE_LAMPORTS: u64 = 5000;
+
+pub async fn run_lifecycle(
+    mut db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let mut proxies = ProxyPool::new(&db, config).await?;
+    let rpc = RpcPool::with_proxies(config, proxies.urls())?;
+    let provider = rpc.direct();
+
+    let mut fee_payers = FeePayerPool::from_config(config)?;
+    let mut order = AccountOrder::from_config(&db, config);
+    let mut failures = FailureLog::read(Step::Lifecycle).await?;
+    let mut report = RunReport::new(Step::Lifecycle);
+
+    let version = config.claim_version;
+    let mut receipts = ReceiptCache::read().await?;
+    let mut deferred = get_offline_skipped(&db, &receipts, config);
+
+    if db.assign_header_profiles(&config.header_profiles) {
+        db.update();
+    }
+
+    let mut cache = AccountCache::new(provider, config);
+    prefetch_leased_accounts(
+        &mut cache,
+        &rpc,
+        &proxies,
+        db.0.iter().filter(|a| {
+            selected.contains(a)
+                && (!a.get_claimed(version) || !a.get_closed_ata() || !a.get_collected_sol())
+        }),
+        config,
+    )
+    .await?;
+
+    while let Some(account) = order.next(&mut db, |a| {
+        selected.contains(a)
+            && (!a.get_claimed(version) || !a.get_closed_ata() || !a.get_collected_sol())
+            && !deferred.contains(&a.get_pubkey())
+    }) {
+        if proxies.is_exhausted() {
+            tracing::error!("Every proxy is out of rotation, stopping");
+            break;
+        }
+
+        let fee_payer = match fee_payers.next(provider).await {
+            Ok(fee_payer) => fee_payer,
+            Err(e) => {
+                tracing::error!("{}", e);
+                break;
+            }
+        };
+
+        let lease = match proxies.get(&account.get_pubkey().to_string(), account.get_proxy()) {
+            Ok(lease) => lease,
+            Err(e) => {
+                tracing::error!("{}", e);
+                break;
+            }
+        };
+
+        // RPC calls of the wallet go through the proxy its Grass API requests use
+        let account_provider = rpc.get(lease.as_ref().map(|lease| lease.url.as_str()));
+        cache.set_provider(account_provider);
+
+        match process_account(
+            account_provider,
+            &mut cache,
+            &mut receipts,
+            &mut proxies,
+            lease.as_ref(),
+            account,
+            fee_payer.as_ref(),
+            config,
+        )
+        .await
+        {
+            Ok(amounts) => {
+                report.processed(&account.get_pubkey(), amounts);
+
+                if account.get_collected_sol() {
+                    failures.resolve(&account.get_pubkey());
+                } else {
+                    deferred.insert(account.get_pubkey());
+                }
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+                failures.record(&account.get_pubkey(), &e);
+                report.failed(&account.get_pubkey(), &e);
+            }
+        };
+
+        db.update();
+
+        pretty_sleep(config.claim_sleep_range).await;
+    }
+
+    report.finish()
+}
+
+/// Claim instructions together with the amount of GRASS they are expected to deliver and the
+/// amount of lamports they take from the wallet.
+struct ClaimPart {
+    version_number: u32,
+    claim_status_pubkey: Pubkey,
+    sent_allocation: u64,
+    expected_amount: u64,
+    completes: bool,
+    wallet_spent: u64,
+    ixs: Vec<Instruction>,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn get_claim_part(
+    provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
+    receipts: &mut ReceiptCache,
+    proxies: &mut ProxyPool,
+    lease: Option<&ProxyLease>,
+    account: &mut Account,
+    payer_pubkey: &Pubkey,
+    config: &Config,
+) -> eyre::Result<Option<ClaimPart>> {
+    let wallet_pubkey = account.get_pubkey();
+
+    let CachedReceipt {
+        version_number,
+        proof,
+        allocation,
+        ..
+    } = match get_claim_receipt(receipts, proxies, lease, account, config).await? {
+        Some(receipt) => receipt,
+        None => {
+            tracing::warn!("No receipt for version {}, skipping", config.claim_version);
+            return Ok(None);
+        }
+    };
+
+    account.set_allocation(version_number, allocation);
+
+    if version_number != config.claim_version {
+        tracing::warn!(
+            "Receipt is issued for distributor version {}, but version {} is targeted",
+            version_number,
+            config.claim_version
+        );
+        return Ok(None);
+    }
+
+    let (merkle_distributor_pubkey, _) = derive_merkle_distributor(version_number);
+    let (claim_status_pubkey, _) = derive_claim_status(&wallet_pubkey, &merkle_distributor_pubkey);
+
+    let (total_allocation, sent_allocation) =
+        match get_sent_allocation(cache, &claim_status_pubkey).await? {
+            Some(status) => status,
+            None => (allocation, 0),
+        };
+
+    account.set_sent_allocation(version_number, sent_allocation);
+
+    if sent_allocation >= total_allocation {
+        tracing::info!("Already claimed");
+        account.set_claimed(version_number, None);
+        return Ok(None);
+    }
+
+    let claim_status_exist = cache.exists(&claim_status_pubkey).await?;
+    let rent = cache.get_rent(CLAIM_STATUS_LEN).await?;
+    let wallet_balance = cache.get_balance(&wallet_pubkey).await?;
+
+    let ixs = get_claim_ixs(
+        cache,
+        version_number,
+        proof,
+        allocation,
+        &wallet_pubkey,
+        payer_pubkey,
+    )
+    .await?
+    .ok_or_else(|| eyre::eyre!("Wallet can't pay for the claim"))?;
+
+    let wallet_spent = match claim_status_exist {
+        true => 0,
+        // the payer tops the wallet up with `rent` when it can't afford the ClaimStatus PDA
+        false if payer_pubkey != &wallet_pubkey && wallet_balance < rent => 0,
+        false => rent,
+    };
+
+    // a vested distributor releases only the unlocked part of the remainder, so the claim is
+    // simulated to learn what the ClaimStatus PDA will record as sent
+    let tx = Transaction::new_with_payer(&ixs, Some(payer_pubkey));
+    let claimed_allocation = match simulate_tx_accounts(provider, &tx, &[claim_status_pubkey])
+        .await?
+        .pop()
+    {
+        Some(Some(claim_status_account)) => read_sent_allocation(&claim_status_account.data)?.1,
+        _ => eyre::bail!("ClaimStatus PDA is missing after the simulated claim"),
+    };
+
+    let expected_amount = claimed_allocation.saturating_sub(sent_allocation);
+
+    if expected_amount == 0 {
+        tracing::info!("Nothing is unlocked to claim yet");
+        return Ok(None);
+    }
+
+    Ok(Some(ClaimPart {
+        version_number,
+        claim_status_pubkey,
+        sent_allocation: claimed_allocation,
+        expected_amount,
+        completes: claimed_allocation >= total_allocation,
+        wallet_spent,
+        ixs,
+    }))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn process_account(
+    provider: &RpcClient,
+    cache: &mut AccountCache<'_>,
+    receipts: &mut ReceiptCache,
+    proxies: &mut ProxyPool,
+    lease: Option<&ProxyLease>,
+    account: &mut Account,
+    fee_payer: Option<&Keypair>,
+    config: &Config,
+) -> eyre::Result<Option<Amounts>> {
+    let wallet = account.keypair();
+    let wallet_pubkey = account.get_pubkey();
+    let cex_pubkey = Pubkey::from_str(account.get_cex_address())?;
+    let collector_pubkey = Pubkey::from_str(&config.collector_pubkey)?;
+
+    tracing::info!("Wallet address: `{}`", wallet.pubkey());
+
+    validate_cex_memo(account.get_cex_memo(), config)?;
+
+    let payer_kp = match fee_payer {
+        Some(fee_payer) => fee_payer.insecure_clone(),
+        None => wallet.insecure_clone(),
+    };
+
+    let signing_keypairs = match fee_payer {
+        Some(_) => vec![&payer_kp, &wallet],
+        None => vec![&wallet],
+    };
+
+    let payer_pubkey = payer_kp.pubkey();
+
+    let claim_part = match account.get_claimed(config.claim_version) {
+        true => None,
+        false => {
+            get_claim_part(
+                provider,
+                cache,
+                receipts,
+                proxies,
+                lease,
+                account,
+                &payer_pubkey,
+                config,
+            )
+            .await?
+        }
+    };
+
+    // without a claim for the targeted version the wallet keeps its SOL for a later run
+    if !account.get_claimed(config.claim_version) && claim_part.is_none() {
+        return Ok(None);
+    }
+
+    let mut ixs = vec![];
+    let mut wallet_spent = 0;
+    let mut wallet_refund = 0;
+    let mut grass_amount = 0;
+    let mut amounts = Amounts::default();
+
+    // a partial claim leaves the ATA and the SOL in place for the next unlock
+    let finishes = match &claim_part {
+        Some(claim_part) => claim_part.completes,
+        None => true,
+    };
+
+    if let Some(claim_part) = &claim_part {
+        tracing::info!(
+            "Amount to claim: {} GRASS",
+            claim_part.expected_amount as f64 / 10f64.powi(9)
+        );
+
+        ixs.extend_from_slice(&claim_part.ixs);
+        wallet_spent += claim_part.wallet_spent;
+        grass_amount += claim_part.expected_amount;
+        amounts.grass_claimed = claim_part.expected_amount;
+    }
+
+    let (wallet_token_ata, _) = derive_ata(&wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
+    let token_balance = cache.get_token_balance(&wallet_token_ata).await?;
+    let token_ata_exist = token_balance.is_some();
+
+    grass_amount += token_balance.unwrap_or(0);
+
+    let ata_rent = cache.get_rent(spl_token::state::Account::LEN).await?;
+
+    let forwards = grass_amount != 0;
+
+    if forwards {
+        let forward_ixs = get_forward_ixs(
+            cache,
+            grass_amount,
+            &wallet_pubkey,
+            &cex_pubkey,
+            account.get_cex_memo(),
+            account.get_split_rules().unwrap_or(&config.split_rules),
+            &payer_pubkey,
+        )
+        .await?;
+
+        if payer_pubkey == wallet_pubkey {
+            // destination ATAs missing on-chain are created from the wallet's SOL
+            let created_atas = forward_ixs
+                .iter()
+                .filter(|ix| ix.program_id == ASSOCIATED_TOKEN_PROGRAM_ID)
+                .count() as u64;
+
+            wallet_spent += created_atas * ata_rent;
+        }
+
+        ixs.extend(forward_ixs);
+        amounts.grass_forwarded = grass_amount;
+    }
+
+    if finishes && (token_ata_exist || claim_part.is_some()) {
+        let close_ixs =
+            Instructions::close_account(&wallet_token_ata, &wallet_pubkey, &payer_pubkey, ata_rent);
+
+        ixs.extend_from_slice(&close_ixs);
+        amounts.rent_reclaimed = ata_rent;
+        amounts.close_fees_paid = get_close_fee(ata_rent);
+
+        if payer_pubkey == wallet_pubkey {
+            // the rent of a pre-existing ATA comes back to the wallet, the close fee goes out
+            if token_ata_exist {
+                wallet_refund += ata_rent;
+            }
+
+            wallet_spent += get_close_fee(ata_rent);
+        }
+    }
+
+    if payer_pubkey == wallet_pubkey {
+        wallet_spent += TX_FEE_LAMPORTS;
+    }
+
+    let balance = cache.get_balance(&wallet_pubkey).await?;
+    let sweep_amount = (balance + wallet_refund).saturating_sub(wallet_spent);
+
+    if finishes && sweep_amount != 0 {
+        ixs.push(solana_sdk::system_instruction::transfer(
+            &wallet_pubkey,
+            &collector_pubkey,
+            sweep_amount,
+        ));
+        amounts.sol_collected = sweep_amount;
+    }
+
+    if ixs.is_empty() {
+        tracing::info!("Nothing left to do");
+    } else {
+        cache.invalidate(&ixs);
+
+        let (recent_blockhash, _) = provider
+            .get_latest_blockhash_with_commitment(CommitmentConfig::finalized())
+            .await?;
+
+        let tx = Transaction::new_signed_with_payer(
+            &ixs,
+            Some(&payer_pubkey),
+            &signing_keypairs,
+            recent_blockhash,
+        );
+
+        let tx_size = bincode::serialize(&tx)?.len();
+
+        if tx_size > PACKET_DATA_SIZE {
+            tracing::warn!(
+                "Lifecycle transaction is too large ({} > {} bytes), use the separate modules for this wallet",
+                tx_size,
+                PACKET_DATA_SIZE
+            );
+            return Ok(None);
+        }
+
+        amounts.fees_paid = get_tx_fee(provider, &tx, &wallet_pubkey).await;
+
+        let signature = send_and_confirm_tx(provider, tx, &recent_blockhash).await?;
+
+        if let Some(claim_part) = &claim_part {
+            record_claim(cache, account, claim_part, &signature).await;
+        }
+    }
+
+    if forwards {
+        account.set_forwarded_grass(true);
+    }
+
+    account.set_closed_ata(finishes);
+    account.set_collected_sol(finishes);
+
+    Ok((!ixs.is_empty()).then_some(amounts))
+}
+
+/// Records a confirmed claim from its simulation, then reconciles it with the ClaimStatus PDA.
+/// The transaction is final at this point, so a failing read only leaves the simulated state.
+async fn record_claim(
+    cache: &mut AccountCache<'_>,
+    account: &mut Account,
+    claim_part: &ClaimPart,
+    signature: &Signature,
+) {
+    let version_number = claim_part.version_number;
+
+    account.set_claim_signature(version_number, signature.to_string());
+    account.set_sent_allocation(version_number, claim_part.sent_allocation);
+
+    if claim_part.completes {
+        account.set_claimed(version_number, Some(signature.to_string()));
+    }
+
+    match get_sent_allocation(cache, &claim_part.claim_status_pubkey).await {
+        Ok(Some((total_allocation, sent_allocation))) => {
+            account.set_sent_allocation(version_number, sent_allocation);
+
+            if sent_allocation >= total_allocation {
+                account.set_claimed(version_number, Some(signature.to_string()));
+            } else {
+                tracing::info!(
+                    "Partially claimed: {} of {} GRASS",
+                    sent_allocation as f64 / 10f64.powi(9),
+                    total_allocation as f64 / 10f64.powi(9)
+                );
+            }
+        }
+        Ok(None) => tracing::warn!("ClaimStatus PDA is missing after the claim"),
+        Err(e) => tracing::warn!("Failed to read ClaimStatus PDA after the claim: {}", e),
+    }
+}
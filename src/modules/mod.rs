@@ -2,14 +2,38 @@ mod claimer;
 mod closer;
 mod collect_and_close;
 mod collector;
+mod disperser;
+mod forwarder;
+mod importer;
+mod lifecycle;
+mod overview;
+mod planner;
+mod prefetch;
+mod receipts;
+mod report;
+mod retry;
+mod sync;
 
-use crate::{config::Config, db::database::Database};
+use crate::{
+    config::Config,
+    db::{database::Database, selection::Selection},
+    utils::fetch::{set_http_policy, HttpPolicy},
+};
 
 use claimer::claim_grass;
 use closer::close_accounts;
 use collect_and_close::collect_and_close;
 use collector::collect_sol;
-use dialoguer::{theme::ColorfulTheme, Select};
+use dialoguer::{theme::ColorfulTheme, Input, Select};
+use disperser::disperse_sol;
+use forwarder::forward_grass;
+use importer::import_proofs;
+use lifecycle::run_lifecycle;
+use overview::show_overview;
+use planner::plan_costs;
+use receipts::prefetch_receipts;
+use retry::retry_failed;
+use sync::sync_database;
 
 const LOGO: &str = r#"
     ___                                                  __
@@ -30,18 +54,36 @@ const LOGO: &str = r#"
                      t.me/fragment_software
 "#;
 
-pub async fn menu() -> eyre::Result<()> {
+/// `selection_spec` comes from the `--select` argument and can be changed from the menu.
+pub async fn menu(selection_spec: Option<String>) -> eyre::Result<()> {
     let config = Config::read_default().await;
 
+    let mut account_selection = match selection_spec {
+        Some(spec) => Selection::parse(&spec).await?,
+        None => Selection::default(),
+    };
+
+    set_http_policy(HttpPolicy::from_config(&config));
+
     println!("{LOGO}");
 
     loop {
         let options = vec![
             "Generate a database for a session",
+            "Show wallets overview",
+            "Plan run costs",
+            "Sync database with the chain",
+            "Prefetch claim receipts",
+            "Import claim proofs",
+            "Disperse SOL",
             "Claim Grass",
+            "Forward Grass to CEX",
             "Close Grass ATA",
             "Collect SOL",
             "Collect Grass + Close Grass ATA + Collect SOL",
+            "Claim + Forward Grass + Close Grass ATA + Collect SOL (single transaction)",
+            "Retry failed wallets",
+            "Select accounts",
             "Exit",
         ];
 
@@ -59,21 +101,76 @@ pub async fn menu() -> eyre::Result<()> {
             }
             1 => {
                 let db = Database::read().await;
-                claim_grass(db, &config).await?;
+                show_overview(db, &account_selection, &config).await?;
             }
             2 => {
                 let db = Database::read().await;
-                close_accounts(db, &config).await?;
+                plan_costs(db, &account_selection, &config).await?;
             }
             3 => {
                 let db = Database::read().await;
-                collect_sol(db, &config).await?;
+                sync_database(db, &account_selection, &config).await?;
             }
             4 => {
                 let db = Database::read().await;
-                collect_and_close(db, &config).await?;
+                prefetch_receipts(db, &account_selection, &config).await?;
             }
             5 => {
+                let db = Database::read().await;
+                import_proofs(db, &account_selection, &config).await?;
+            }
+            6 => {
+                let db = Database::read().await;
+                disperse_sol(db, &account_selection, &config).await?;
+            }
+            7 => {
+                let db = Database::read().await;
+                claim_grass(db, &account_selection, &config).await?;
+            }
+            8 => {
+                let db = Database::read().await;
+                forward_grass(db, &account_selection, &config).await?;
+            }
+            9 => {
+                let db = Database::read().await;
+                close_accounts(db, &account_selection, &config).await?;
+            }
+            10 => {
+                let db = Database::read().await;
+                collect_sol(db, &account_selection, &config).await?;
+            }
+            11 => {
+                let db = Database::read().await;
+                collect_and_close(db, &account_selection, &config).await?;
+            }
+            12 => {
+                let db = Database::read().await;
+                run_lifecycle(db, &account_selection, &config).await?;
+            }
+            13 => {
+                retry_failed(&config).await?;
+            }
+            14 => {
+                let spec = Input::<String>::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Addresses, @file, ranges (1-20), tag:<tag>, status:<status>")
+                    .with_initial_text(account_selection.get_spec())
+                    .allow_empty(true)
+                    .interact_text()
+                    .unwrap();
+
+                match Selection::parse(&spec).await {
+                    Ok(parsed) if parsed.is_empty() => {
+                        account_selection = parsed;
+                        tracing::info!("Selection cleared, every account is used")
+                    }
+                    Ok(parsed) => {
+                        account_selection = parsed;
+                        tracing::info!("Selection set: `{}`", account_selection.get_spec())
+                    }
+                    Err(e) => tracing::error!("Invalid selection: {e}"),
+                }
+            }
+            15 => {
                 return Ok(());
             }
             _ => tracing::error!("Invalid selection"),
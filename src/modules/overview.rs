@@ -0,0 +1,212 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use borsh::BorshDeserialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig, native_token::lamports_to_sol, program_pack::Pack,
+};
+
+use crate::{
+    config::Config,
+    db::{account::Account, database::Database, selection::Selection},
+    onchain::{
+        accounts::get_multiple_accounts_chunked,
+        constants::{GRASS_PUBKEY, TOKEN_PROGRAM_ID},
+        derive::{derive_ata, derive_claim_status, derive_merkle_distributor},
+        state::ClaimStatus,
+    },
+};
+
+/// Chain state of a single wallet, as seen by one batched `getMultipleAccounts` pass.
+pub(super) struct WalletState {
+    pub lamports: u64,
+    pub token_ata_exist: bool,
+    pub grass_balance: u64,
+    /// `(allocation, sent_allocation)` of every ClaimStatus PDA that exists.
+    pub claim_statuses: BTreeMap<u32, (u64, u64)>,
+}
+
+pub(super) fn get_versions(accounts: &[Account], config: &Config) -> BTreeSet<u32> {
+    accounts
+        .iter()
+        .flat_map(|account| account.get_claim_versions())
+        .chain(std::iter::once(config.claim_version))
+        .collect()
+}
+
+pub(super) async fn get_wallet_states(
+    provider: &RpcClient,
+    accounts: &[Account],
+    versions: &BTreeSet<u32>,
+) -> eyre::Result<Vec<WalletState>> {
+    let distributors = versions
+        .iter()
+        .map(|&version| (version, derive_merkle_distributor(version).0))
+        .collect::<Vec<_>>();
+
+    let mut pubkeys = vec![];
+
+    for account in accounts {
+        let wallet_pubkey = account.get_pubkey();
+
+        pubkeys.push(wallet_pubkey);
+        pubkeys.push(derive_ata(&wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID).0);
+
+        for (_, distributor) in &distributors {
+            pubkeys.push(derive_claim_status(&wallet_pubkey, distributor).0);
+        }
+    }
+
+    let chain_accounts = get_multiple_accounts_chunked(provider, &pubkeys).await?;
+
+    let mut states = Vec::with_capacity(accounts.len());
+
+    for wallet_pubkeys in pubkeys.chunks(2 + distributors.len()) {
+        let lamports = chain_accounts
+            .get(&wallet_pubkeys[0])
+            .map_or(0, |account| account.lamports);
+
+        let token_account = chain_accounts
+            .get(&wallet_pubkeys[1])
+            .map(|account| spl_token::state::Account::unpack(&account.data))
+            .transpose()?;
+
+        let mut claim_statuses = BTreeMap::new();
+
+        for ((version, _), claim_status_pubkey) in distributors.iter().zip(&wallet_pubkeys[2..]) {
+            if let Some(account) = chain_accounts.get(claim_status_pubkey) {
+                let claim_status = ClaimStatus::deserialize(&mut &account.data[8..])?;
+                claim_statuses.insert(
+                    *version,
+                    (claim_status.allocation, claim_status.sent_allocation),
+                );
+            }
+        }
+
+        states.push(WalletState {
+            lamports,
+            token_ata_exist: token_account.is_some(),
+            grass_balance: token_account.map_or(0, |account| account.amount),
+            claim_statuses,
+        });
+    }
+
+    Ok(states)
+}
+
+fn get_mismatches(account: &Account, state: &WalletState, versions: &BTreeSet<u32>) -> Vec<String> {
+    let mut mismatches = vec![];
+
+    for &version in versions {
+        let claimed_onchain = state
+            .claim_statuses
+            .get(&version)
+            .is_some_and(|(allocation, sent)| sent >= allocation);
+        let has_allocation = account
+            .get_claim(version)
+            .is_some_and(|claim| claim.allocation != 0);
+
+        if account.get_claimed(version) && has_allocation && !claimed_onchain {
+            mismatches.push(format!("v{version} claimed in db, but not on chain"));
+        }
+
+        if !account.get_claimed(version) && claimed_onchain {
+            mismatches.push(format!("v{version} claimed on chain, but not in db"));
+        }
+    }
+
+    if account.get_closed_ata() && state.token_ata_exist {
+        mismatches.push("ATA closed in db, but exists on chain".to_string());
+    }
+
+    if account.get_forwarded_grass() && state.grass_balance != 0 {
+        mismatches.push("GRASS forwarded in db, but the ATA holds a balance".to_string());
+    }
+
+    if account.get_collected_sol() && state.lamports > 5000 {
+        mismatches.push("SOL collected in db, but the wallet holds a balance".to_string());
+    }
+
+    mismatches
+}
+
+pub async fn show_overview(
+    db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+    let accounts =
+        db.0.into_iter()
+            .filter(|a| selected.contains(a))
+            .collect::<Vec<_>>();
+
+    let provider = RpcClient::new_with_timeout_and_commitment(
+        config.solana_rpc_url.clone(),
+        Duration::from_secs(60),
+        CommitmentConfig::processed(),
+    );
+
+    let versions = get_versions(&accounts, config);
+    let states = get_wallet_states(&provider, &accounts, &versions).await?;
+
+    let mut total_lamports = 0;
+    let mut total_grass = 0;
+    let mut open_atas = 0;
+    let mut mismatched_wallets = 0;
+
+    for (account, state) in accounts.iter().zip(&states) {
+        let claim_statuses = versions
+            .iter()
+            .map(|version| match state.claim_statuses.get(version) {
+                Some((allocation, sent)) => format!(
+                    "v{version}: {}/{}",
+                    *sent as f64 / 10f64.powi(9),
+                    *allocation as f64 / 10f64.powi(9)
+                ),
+                None => format!("v{version}: -"),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        tracing::info!(
+            "`{}` | SOL: {} | GRASS: {} | ATA: {} | ClaimStatus: [{}] | db: claimed v{}={}, forwarded={}, closed_ata={}, collected_sol={}",
+            account.get_pubkey(),
+            lamports_to_sol(state.lamports),
+            state.grass_balance as f64 / 10f64.powi(9),
+            if state.token_ata_exist { "open" } else { "none" },
+            claim_statuses,
+            config.claim_version,
+            account.get_claimed(config.claim_version),
+            account.get_forwarded_grass(),
+            account.get_closed_ata(),
+            account.get_collected_sol()
+        );
+
+        let mismatches = get_mismatches(account, state, &versions);
+
+        if !mismatches.is_empty() {
+            mismatched_wallets += 1;
+            tracing::warn!(
+                "`{}` db flags don't match the chain: {}",
+                account.get_pubkey(),
+                mismatches.join("; ")
+            );
+        }
+
+        total_lamports += state.lamports;
+        total_grass += state.grass_balance;
+        open_atas += usize::from(state.token_ata_exist);
+    }
+
+    tracing::info!("Wallets: {}", accounts.len());
+    tracing::info!("Total SOL: {}", lamports_to_sol(total_lamports));
+    tracing::info!("Total GRASS: {}", total_grass as f64 / 10f64.powi(9));
+    tracing::info!("Open GRASS ATAs: {}", open_atas);
+    tracing::info!("Wallets with mismatched db flags: {}", mismatched_wallets);
+
+    Ok(())
+}
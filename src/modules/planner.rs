@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+
+use solana_sdk::{
+    native_token::{lamports_to_sol, sol_to_lamports},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+use super::{
+    claimer::get_claim_accounts, forwarder::get_destinations, prefetch::prefetch_accounts,
+};
+
+use crate::{
+    config::Config,
+    db::{account::Account, database::Database, selection::Selection},
+    onchain::{
+        cache::AccountCache,
+        constants::{GRASS_PUBKEY, TOKEN_PROGRAM_ID},
+        derive::derive_ata,
+        fee_payer::FeePayerPool,
+        state::CLAIM_STATUS_LEN,
+        transport::RpcPool,
+    },
+};
+
+const SIGNATURE_FEE_LAMPORTS: u64 = 5000;
+
+#[derive(Default)]
+pub(super) struct CostPlan {
+    ata_creations: usize,
+    claim_status_creations: usize,
+    destination_ata_creations: usize,
+    rent: u64,
+    base_fees: u64,
+    // transactions are sent without a compute unit price
+    priority_fees: u64,
+    close_fees: u64,
+    payer_cost: u64,
+}
+
+impl CostPlan {
+    fn total(&self) -> u64 {
+        self.rent + self.base_fees + self.priority_fees + self.close_fees
+    }
+}
+
+/// Lamports one wallet's operations take from the external fee payer and from the wallet itself.
+#[derive(Default)]
+pub(super) struct WalletCost {
+    pub payer: u64,
+    pub wallet: u64,
+    pub wallet_balance: u64,
+}
+
+impl WalletCost {
+    fn charge(&mut self, external_payer: bool, lamports: u64) {
+        match external_payer {
+            true => self.payer += lamports,
+            false => self.wallet += lamports,
+        }
+    }
+}
+
+/// Rent and fees every wallet of the run is charged with.
+#[derive(Clone, Copy)]
+pub(super) struct Prices {
+    tx_fee: u64,
+    ata_rent: u64,
+    claim_status_rent: u64,
+    close_fee: u64,
+}
+
+impl Prices {
+    pub async fn fetch(cache: &mut AccountCache<'_>, config: &Config) -> eyre::Result<Self> {
+        let signatures = if config.use_external_fee_pay { 2 } else { 1 };
+        let ata_rent = cache.get_rent(spl_token::state::Account::LEN).await?;
+
+        Ok(Self {
+            tx_fee: SIGNATURE_FEE_LAMPORTS * signatures,
+            ata_rent,
+            claim_status_rent: cache.get_rent(CLAIM_STATUS_LEN).await?,
+            close_fee: sol_to_lamports(lamports_to_sol(ata_rent) * 0.03),
+        })
+    }
+}
+
+/// Adds the rent and fees of the wallet's remaining operations to `plan` and returns the part the
+/// external fee payer and the wallet itself pay. Destination ATAs already in
+/// `checked_destination_atas` are counted by an earlier wallet.
+pub(super) async fn plan_wallet(
+    cache: &mut AccountCache<'_>,
+    prices: &Prices,
+    account: &Account,
+    checked_destination_atas: &mut HashSet<Pubkey>,
+    plan: &mut CostPlan,
+    config: &Config,
+) -> eyre::Result<WalletCost> {
+    let Prices {
+        tx_fee,
+        ata_rent,
+        claim_status_rent,
+        close_fee,
+    } = *prices;
+
+    let version = config.claim_version;
+    let external_payer = config.use_external_fee_pay;
+
+    let wallet_pubkey = account.get_pubkey();
+    let claim_accounts = get_claim_accounts(cache, version, &wallet_pubkey).await?;
+    let mut cost = WalletCost {
+        wallet_balance: claim_accounts.wallet_balance,
+        ..Default::default()
+    };
+
+    if !account.get_claimed(version) {
+        if !claim_accounts.token_ata_exist {
+            plan.ata_creations += 1;
+            plan.rent += ata_rent;
+            cost.charge(external_payer, ata_rent);
+        }
+
+        if !claim_accounts.claim_status_exist {
+            plan.claim_status_creations += 1;
+            plan.rent += claim_status_rent;
+
+            // the payer tops up wallets that can't afford the ClaimStatus PDA themselves
+            let payer_tops_up = external_payer && claim_accounts.wallet_balance < claim_status_rent;
+            cost.charge(payer_tops_up, claim_status_rent);
+        }
+
+        plan.base_fees += tx_fee;
+        cost.charge(external_payer, tx_fee);
+
+        if config.withdraw_to_cex {
+            for destination in get_destinations(account, config)? {
+                let (destination_token_ata, _) =
+                    derive_ata(&destination, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID);
+
+                if checked_destination_atas.insert(destination_token_ata)
+                    && !cache.exists(&destination_token_ata).await?
+                {
+                    plan.destination_ata_creations += 1;
+                    plan.rent += ata_rent;
+                    cost.charge(external_payer, ata_rent);
+                }
+            }
+
+            plan.base_fees += tx_fee;
+            cost.charge(external_payer, tx_fee);
+        }
+    }
+
+    let has_token_ata = claim_accounts.token_ata_exist || !account.get_claimed(version);
+
+    if !account.get_closed_ata() && has_token_ata {
+        plan.close_fees += close_fee;
+        plan.base_fees += tx_fee;
+        cost.charge(external_payer, close_fee + tx_fee);
+    }
+
+    if !account.get_collected_sol() {
+        plan.base_fees += tx_fee;
+        cost.charge(external_payer, tx_fee);
+    }
+
+    plan.payer_cost += cost.payer;
+
+    Ok(cost)
+}
+
+pub async fn plan_costs(db: Database, selection: &Selection, config: &Config) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let rpc = RpcPool::new(config, &db)?;
+    let provider = rpc.direct();
+
+    let version = config.claim_version;
+    let fee_payers = FeePayerPool::from_config(config)?;
+
+    let mut cache = AccountCache::new(provider, config);
+    prefetch_accounts(
+        &mut cache,
+        &rpc,
+        db.0.iter().filter(|a| {
+            selected.contains(a)
+                && (!a.get_claimed(version) || !a.get_closed_ata() || !a.get_collected_sol())
+        }),
+        config,
+    )
+    .await?;
+
+    let prices = Prices::fetch(&mut cache, config).await?;
+
+    let mut plan = CostPlan::default();
+    let mut checked_destination_atas = HashSet::new();
+    let mut underfunded_wallets = vec![];
+
+    for account in db.0.iter().filter(|a| {
+        selected.contains(a)
+            && (!a.get_claimed(version) || !a.get_closed_ata() || !a.get_collected_sol())
+    }) {
+        let cost = plan_wallet(
+            &mut cache,
+            &prices,
+            account,
+            &mut checked_destination_atas,
+            &mut plan,
+            config,
+        )
+        .await?;
+
+        if cost.wallet > cost.wallet_balance {
+            underfunded_wallets.push((account.get_pubkey(), cost.wallet_balance, cost.wallet));
+        }
+    }
+
+    tracing::info!(
+        "Associated token accounts to create: {}",
+        plan.ata_creations
+    );
+    tracing::info!(
+        "ClaimStatus PDAs to create: {}",
+        plan.claim_status_creations
+    );
+    tracing::info!(
+        "Destination token accounts to create: {}",
+        plan.destination_ata_creations
+    );
+    tracing::info!("Rent: {} SOL", lamports_to_sol(plan.rent));
+    tracing::info!("Base fees: {} SOL", lamports_to_sol(plan.base_fees));
+    tracing::info!("Priority fees: {} SOL", lamports_to_sol(plan.priority_fees));
+    tracing::info!("Close fees: {} SOL", lamports_to_sol(plan.close_fees));
+    tracing::info!("Total: {} SOL", lamports_to_sol(plan.total()));
+
+    if config.use_external_fee_pay {
+        let mut payers_balance = 0;
+
+        for payer_pubkey in fee_payers.pubkeys() {
+            payers_balance += provider.get_balance(&payer_pubkey).await?;
+        }
+
+        if payers_balance < plan.payer_cost {
+            tracing::warn!(
+                "Fee payers don't have enough SOL: {} | {}",
+                lamports_to_sol(payers_balance),
+                lamports_to_sol(plan.payer_cost)
+            );
+        } else {
+            tracing::info!(
+                "Fee payers have enough SOL: {} | {}",
+                lamports_to_sol(payers_balance),
+                lamports_to_sol(plan.payer_cost)
+            );
+        }
+    }
+
+    for (wallet_pubkey, balance, required) in &underfunded_wallets {
+        tracing::warn!(
+            "Wallet `{}` doesn't have enough SOL: {} | {}",
+            wallet_pubkey,
+            lamports_to_sol(*balance),
+            lamports_to_sol(*required)
+        );
+    }
+
+    if underfunded_wallets.is_empty() {
+        tracing::info!("Every wallet can afford its own part of the run");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use solana_sdk::pubkey::Pubkey;
+
+use super::forwarder::get_destinations;
+
+use crate::{
+    config::Config,
+    db::account::Account,
+    onchain::{
+        cache::AccountCache,
+        constants::{GRASS_PUBKEY, TOKEN_PROGRAM_ID},
+        derive::{derive_ata, derive_claim_status, derive_merkle_distributor},
+        transport::RpcPool,
+    },
+    utils::proxy_pool::ProxyPool,
+};
+
+/// Addresses read by the pre-checks of the modules for a single account.
+fn get_account_pubkeys(account: &Account, config: &Config) -> Vec<Pubkey> {
+    let wallet_pubkey = account.get_pubkey();
+    let (merkle_distributor_pubkey, _) = derive_merkle_distributor(config.claim_version);
+
+    let mut pubkeys = vec![
+        wallet_pubkey,
+        derive_ata(&wallet_pubkey, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID).0,
+        derive_claim_status(&wallet_pubkey, &merkle_distributor_pubkey).0,
+    ];
+
+    if let Ok(destinations) = get_destinations(account, config) {
+        pubkeys.extend(
+            destinations
+                .iter()
+                .map(|destination| derive_ata(destination, &GRASS_PUBKEY, &TOKEN_PROGRAM_ID).0),
+        );
+    }
+
+    pubkeys
+}
+
+/// Loads the accounts in batches. When RPC reads are proxied, every batch only holds the
+/// accounts of wallets sharing one proxy and is sent through it.
+pub(super) async fn prefetch_accounts<'a, 'r>(
+    cache: &mut AccountCache<'r>,
+    rpc: &'r RpcPool,
+    accounts: impl Iterator<Item = &'a Account>,
+    config: &Config,
+) -> eyre::Result<()> {
+    let accounts = accounts.map(|account| (account.get_proxy().map(str::to_string), account));
+
+    prefetch_by_proxy(cache, rpc, accounts, config).await
+}
+
+/// Same as `prefetch_accounts`, but reads the accounts of every wallet through the proxy the pool
+/// would lease to it, which its Grass API requests go through as well. Peeking keeps the use
+/// counts and sticky bindings of the pool untouched.
+pub(super) async fn prefetch_leased_accounts<'a, 'r>(
+    cache: &mut AccountCache<'r>,
+    rpc: &'r RpcPool,
+    proxies: &ProxyPool,
+    accounts: impl Iterator<Item = &'a Account>,
+    config: &Config,
+) -> eyre::Result<()> {
+    let accounts = accounts.map(|account| {
+        let proxy = proxies.peek(&account.get_pubkey().to_string(), account.get_proxy());
+        (proxy.map(str::to_string), account)
+    });
+
+    prefetch_by_proxy(cache, rpc, accounts, config).await
+}
+
+async fn prefetch_by_proxy<'a, 'r>(
+    cache: &mut AccountCache<'r>,
+    rpc: &'r RpcPool,
+    accounts: impl Iterator<Item = (Option<String>, &'a Account)>,
+    config: &Config,
+) -> eyre::Result<()> {
+    let mut pubkeys_by_proxy: BTreeMap<Option<String>, Vec<Pubkey>> = BTreeMap::new();
+
+    for (proxy, account) in accounts {
+        let proxy = proxy.filter(|_| rpc.proxy_reads());
+
+        pubkeys_by_proxy
+            .entry(proxy)
+            .or_default()
+            .extend(get_account_pubkeys(account, config));
+    }
+
+    for (proxy, mut pubkeys) in pubkeys_by_proxy {
+        pubkeys.sort();
+        pubkeys.dedup();
+
+        cache.set_provider(rpc.get(proxy.as_deref()));
+        cache.prefetch(&pubkeys).await?;
+    }
+
+    cache.set_provider(rpc.direct());
+
+    Ok(())
+}
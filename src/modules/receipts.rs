@@ -0,0 +1,378 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use solana_sdk::pubkey::Pubkey;
+use tokio::task::JoinSet;
+
+use crate::{
+    config::{Config, HeaderProfile},
+    db::{
+        account::Account,
+        database::Database,
+        failures::{FailureLog, Step},
+        selection::Selection,
+    },
+    getgrass::{
+        api::get_receipt,
+        cache::{CachedReceipt, ReceiptCache},
+        schemas::{ClaimProofEntry, GrassApiResponse, Receipt},
+        typedefs::Cluster,
+    },
+    utils::{
+        proxy::IpRotation,
+        proxy_pool::{ProxyLease, ProxyPool},
+    },
+};
+
+fn prepare_proof(claim_proof_json: &str) -> Vec<[u8; 32]> {
+    if let Ok(claim_proof_array) = serde_json::from_str::<Vec<ClaimProofEntry>>(claim_proof_json) {
+        claim_proof_array
+            .into_iter()
+            .filter_map(|entry| {
+                if entry.data.type_ != "Buffer" {
+                    return None;
+                }
+
+                let data_bytes = entry.data.data;
+
+                if data_bytes.len() != 32 {
+                    return None;
+                }
+
+                let mut buffer = [0u8; 32];
+                buffer.copy_from_slice(&data_bytes);
+                Some(buffer)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    }
+}
+
+fn extract_version_and_proof(
+    receipt: &GrassApiResponse<Receipt>,
+) -> eyre::Result<(u32, Vec<[u8; 32]>, u64)> {
+    let result = receipt
+        .result
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("Receipt result is missing"))?;
+    let data = result
+        .data
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("Data is missing in the receipt result"))?;
+    let version_number = data
+        .version_number
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("Version number is missing in the receipt data"))?;
+    let claim_proof = data
+        .claim_proof
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("Claim proof is missing in the receipt data"))?;
+    let proof = prepare_proof(claim_proof);
+
+    let allocation = data
+        .allocation
+        .as_ref()
+        .ok_or_else(|| eyre::eyre!("Allocation is missing in the receipt data"))?;
+    Ok((*version_number, proof, *allocation))
+}
+
+/// Wallet address, exit IP the receipt was fetched from and the fetch outcome.
+type FetchResult = (String, Option<String>, eyre::Result<Option<CachedReceipt>>);
+
+/// Wallets sharing one proxy, with their header profiles, and the swap link that rotates it.
+#[derive(Default)]
+struct ProxyGroup {
+    exit_ip: Option<String>,
+    swap_link: Option<String>,
+    wallets: Vec<(String, Option<HeaderProfile>)>,
+}
+
+pub async fn prefetch_receipts(
+    mut db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let mut receipts = ReceiptCache::read().await?;
+    let mut proxies = ProxyPool::new(&db, config).await?;
+    let mut failures = FailureLog::read(Step::Receipt).await?;
+    let rotation = IpRotation::from_config(config);
+    let version = config.claim_version;
+
+    if db.assign_header_profiles(&config.header_profiles) {
+        db.update();
+    }
+
+    let mut groups: HashMap<Option<String>, ProxyGroup> = HashMap::new();
+
+    for account in
+        db.0.iter()
+            .filter(|a| selected.contains(a) && !a.get_claimed(version))
+    {
+        let wallet_address = account.get_pubkey().to_string();
+
+        if receipts.contains(&wallet_address, Cluster::Mainnet, version) {
+            continue;
+        }
+
+        let (proxy, exit_ip, swap_link) = match proxies.get(&wallet_address, account.get_proxy())? {
+            Some(lease) => (Some(lease.url), Some(lease.exit_ip), lease.swap_link),
+            None => (None, None, get_direct_swap_link(config)),
+        };
+
+        let group = groups.entry(proxy).or_insert_with(|| ProxyGroup {
+            exit_ip,
+            swap_link,
+            ..Default::default()
+        });
+
+        group
+            .wallets
+            .push((wallet_address, account.get_header_profile().cloned()));
+    }
+
+    let pending = groups
+        .values()
+        .map(|group| group.wallets.len())
+        .sum::<usize>();
+
+    if pending == 0 {
+        tracing::info!("All receipts are already cached");
+        return Ok(());
+    }
+
+    tracing::info!(
+        "Fetching {} receipts through {} proxies",
+        pending,
+        groups.len()
+    );
+
+    let mut tasks = JoinSet::new();
+
+    for (proxy, group) in groups {
+        tasks.spawn(fetch_receipts(proxy, group, rotation.clone()));
+    }
+
+    let mut fetched = 0;
+
+    while let Some(results) = tasks.join_next().await {
+        let (proxy, results) = results?;
+
+        for (wallet_address, exit_ip, result) in results {
+            if let Some(proxy) = &proxy {
+                match result.is_ok() {
+                    true => proxies.report_success(proxy),
+                    false => proxies.report_failure(proxy),
+                }
+            }
+
+            if let Some(exit_ip) = exit_ip {
+                if let Some(account) =
+                    db.0.iter_mut()
+                        .find(|a| a.get_pubkey().to_string() == wallet_address)
+                {
+                    account.set_exit_ip(exit_ip);
+                }
+            }
+
+            let Ok(wallet_pubkey) = Pubkey::from_str(&wallet_address) else {
+                continue;
+            };
+
+            match result {
+                Ok(Some(receipt)) => {
+                    receipts.insert(&wallet_address, Cluster::Mainnet, receipt);
+                    failures.resolve(&wallet_pubkey);
+                    fetched += 1;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::error!("Failed to fetch receipt for `{wallet_address}`: {e}");
+                    failures.record(&wallet_pubkey, &e);
+                }
+            }
+        }
+
+        receipts.update();
+        db.update();
+    }
+
+    tracing::info!("Receipts cached: {} | {}", fetched, pending);
+
+    Ok(())
+}
+
+/// Swap link of requests going out without a proxy, when the machine itself is on a mobile
+/// connection.
+fn get_direct_swap_link(config: &Config) -> Option<String> {
+    (config.mobile_proxies && !config.swap_ip_link.is_empty()).then(|| config.swap_ip_link.clone())
+}
+
+/// Fetches the receipts of the wallets sharing one proxy, one after another. Mobile proxies are
+/// rotated before every request.
+async fn fetch_receipts(
+    proxy: Option<String>,
+    group: ProxyGroup,
+    rotation: IpRotation,
+) -> (Option<String>, Vec<FetchResult>) {
+    let mut results = Vec::with_capacity(group.wallets.len());
+    let mut exit_ip = group.exit_ip;
+
+    for (wallet_address, header_profile) in group.wallets {
+        if let Some(swap_link) = &group.swap_link {
+            match rotation.rotate(proxy.as_deref(), swap_link).await {
+                Ok(ip) => exit_ip = Some(ip),
+                Err(e) => {
+                    results.push((wallet_address, None, Err(e)));
+                    continue;
+                }
+            }
+        }
+
+        let result = fetch_receipt(
+            &wallet_address,
+            Cluster::Mainnet,
+            proxy.as_deref(),
+            header_profile.as_ref(),
+        )
+        .await;
+        results.push((wallet_address, exit_ip.clone(), result));
+    }
+
+    (proxy, results)
+}
+
+/// Returns the receipt of the account from the cache, fetching it through the proxy leased to the
+/// wallet on a miss. `None` means the Grass API has no claimable receipt for the wallet.
+pub(super) async fn get_claim_receipt(
+    receipts: &mut ReceiptCache,
+    proxies: &mut ProxyPool,
+    lease: Option<&ProxyLease>,
+    account: &mut Account,
+    config: &Config,
+) -> eyre::Result<Option<CachedReceipt>> {
+    let wallet_address = account.get_pubkey().to_string();
+
+    if let Some(receipt) = receipts.get(&wallet_address, Cluster::Mainnet, config.claim_version) {
+        tracing::info!("Using cached receipt");
+        return Ok(Some(receipt.clone()));
+    }
+
+    if config.offline_claim {
+        eyre::bail!(
+            "No cached or imported receipt of version {} for the wallet in offline mode",
+            config.claim_version
+        );
+    }
+
+    let proxy = lease.map(|lease| lease.url.as_str());
+
+    let (mut exit_ip, swap_link) = match lease {
+        Some(lease) => (Some(lease.exit_ip.clone()), lease.swap_link.clone()),
+        None => (None, get_direct_swap_link(config)),
+    };
+
+    let result = match swap_link {
+        Some(swap_link) => {
+            tracing::info!("Changing IP address");
+            IpRotation::from_config(config)
+                .rotate(proxy, &swap_link)
+                .await
+                .map(|ip| exit_ip = Some(ip))
+        }
+        None => Ok(()),
+    };
+
+    let result = match result {
+        Ok(()) => {
+            fetch_receipt(
+                &wallet_address,
+                Cluster::Mainnet,
+                proxy,
+                account.get_header_profile(),
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Some(proxy) = proxy {
+        match result.is_ok() {
+            true => proxies.report_success(proxy),
+            false => proxies.report_failure(proxy),
+        }
+
+        if let Some(exit_ip) = &exit_ip {
+            proxies.set_exit_ip(proxy, exit_ip.clone());
+        }
+    }
+
+    if let Some(exit_ip) = exit_ip {
+        account.set_exit_ip(exit_ip);
+    }
+
+    let receipt = result?;
+
+    if let Some(receipt) = &receipt {
+        receipts.insert(&wallet_address, Cluster::Mainnet, receipt.clone());
+        receipts.update();
+    }
+
+    Ok(receipt)
+}
+
+/// Wallets without a cached or imported receipt, which can't be claimed in offline mode.
+pub(super) fn get_offline_skipped(
+    db: &Database,
+    receipts: &ReceiptCache,
+    config: &Config,
+) -> HashSet<Pubkey> {
+    if !config.offline_claim {
+        return HashSet::new();
+    }
+
+    let skipped =
+        db.0.iter()
+            .filter(|a| !a.get_claimed(config.claim_version))
+            .map(|a| a.get_pubkey())
+            .filter(|pubkey| {
+                !receipts.contains(&pubkey.to_string(), Cluster::Mainnet, config.claim_version)
+            })
+            .collect::<HashSet<_>>();
+
+    if !skipped.is_empty() {
+        tracing::warn!(
+            "Offline mode: skipping {} wallets without a cached or imported receipt of the targeted version",
+            skipped.len()
+        );
+    }
+
+    skipped
+}
+
+async fn fetch_receipt(
+    wallet_address: &str,
+    cluster: Cluster,
+    proxy: Option<&str>,
+    header_profile: Option<&HeaderProfile>,
+) -> eyre::Result<Option<CachedReceipt>> {
+    let raw = get_receipt(wallet_address, cluster, proxy, header_profile).await?;
+    let receipt = serde_json::from_value::<GrassApiResponse<Receipt>>(raw.clone())?;
+
+    match extract_version_and_proof(&receipt) {
+        Ok((version_number, proof, allocation)) => Ok(Some(CachedReceipt {
+            raw,
+            version_number,
+            proof,
+            allocation,
+        })),
+        Err(e) => {
+            tracing::error!("No claimable receipt for `{}`: {}", wallet_address, e);
+            Ok(None)
+        }
+    }
+}
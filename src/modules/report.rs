@@ -0,0 +1,286 @@
+use std::{
+    fmt::Write,
+    ops::AddAssign,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{native_token::lamports_to_sol, pubkey::Pubkey, transaction::Transaction};
+
+use crate::db::failures::Step;
+
+const REPORTS_DIR_PATH: &str = "data/reports";
+const SIGNATURE_FEE_LAMPORTS: u64 = 5000;
+
+/// Fee the wallet pays for the transaction, priority fees included. A transaction paid by an
+/// external fee payer costs the wallet nothing.
+pub(super) async fn get_tx_fee(
+    provider: &RpcClient,
+    tx: &Transaction,
+    wallet_pubkey: &Pubkey,
+) -> u64 {
+    if tx.message.account_keys.first() != Some(wallet_pubkey) {
+        return 0;
+    }
+
+    match provider.get_fee_for_message(&tx.message).await {
+        Ok(fee) => fee,
+        Err(e) => {
+            tracing::warn!("Failed to get the transaction fee, counting signatures only: {e}");
+            SIGNATURE_FEE_LAMPORTS * tx.signatures.len() as u64
+        }
+    }
+}
+
+fn get_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn to_grass(amount: u64) -> f64 {
+    amount as f64 / 10f64.powi(9)
+}
+
+/// What a wallet's transactions moved, in lamports and the smallest GRASS units.
+#[derive(Serialize, Debug, Default, Clone, Copy)]
+pub(super) struct Amounts {
+    pub grass_claimed: u64,
+    pub grass_forwarded: u64,
+    pub sol_collected: u64,
+    pub rent_reclaimed: u64,
+    pub fees_paid: u64,
+    pub close_fees_paid: u64,
+}
+
+impl AddAssign for Amounts {
+    fn add_assign(&mut self, other: Self) {
+        self.grass_claimed += other.grass_claimed;
+        self.grass_forwarded += other.grass_forwarded;
+        self.sol_collected += other.sol_collected;
+        self.rent_reclaimed += other.rent_reclaimed;
+        self.fees_paid += other.fees_paid;
+        self.close_fees_paid += other.close_fees_paid;
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    Succeeded,
+    Skipped,
+    Failed,
+}
+
+impl Outcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Succeeded => "succeeded",
+            Self::Skipped => "skipped",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AccountReport {
+    address: String,
+    outcome: Outcome,
+    attempts: u32,
+    error: Option<String>,
+    #[serde(flatten)]
+    amounts: Amounts,
+}
+
+/// Outcome of every wallet a run went through. A wallet retried within the run keeps the outcome
+/// of its last attempt and the amounts of all of them.
+#[derive(Serialize)]
+pub(super) struct RunReport {
+    operation: Step,
+    started_at: u64,
+    finished_at: u64,
+    processed: usize,
+    succeeded: usize,
+    skipped: usize,
+    failed: usize,
+    totals: Amounts,
+    accounts: Vec<AccountReport>,
+}
+
+impl RunReport {
+    pub fn new(operation: Step) -> Self {
+        Self {
+            operation,
+            started_at: get_timestamp(),
+            finished_at: 0,
+            processed: 0,
+            succeeded: 0,
+            skipped: 0,
+            failed: 0,
+            totals: Amounts::default(),
+            accounts: vec![],
+        }
+    }
+
+    fn get_entry(&mut self, wallet_pubkey: &Pubkey) -> &mut AccountReport {
+        let address = wallet_pubkey.to_string();
+
+        let index = match self.accounts.iter().position(|a| a.address == address) {
+            Some(index) => index,
+            None => {
+                self.accounts.push(AccountReport {
+                    address,
+                    outcome: Outcome::Skipped,
+                    attempts: 0,
+                    error: None,
+                    amounts: Amounts::default(),
+                });
+                self.accounts.len() - 1
+            }
+        };
+
+        let entry = &mut self.accounts[index];
+        entry.attempts += 1;
+        entry
+    }
+
+    /// `None` amounts mean the wallet had nothing to do in this step.
+    pub fn processed(&mut self, wallet_pubkey: &Pubkey, amounts: Option<Amounts>) {
+        let entry = self.get_entry(wallet_pubkey);
+        entry.error = None;
+
+        match amounts {
+            Some(amounts) => {
+                entry.outcome = Outcome::Succeeded;
+                entry.amounts += amounts;
+            }
+            None => entry.outcome = Outcome::Skipped,
+        }
+    }
+
+    pub fn failed(&mut self, wallet_pubkey: &Pubkey, e: &eyre::Report) {
+        self.failed_after(wallet_pubkey, Amounts::default(), e);
+    }
+
+    /// Keeps what the wallet moved before it failed, like a claim whose forward didn't go through.
+    pub fn failed_after(&mut self, wallet_pubkey: &Pubkey, amounts: Amounts, e: &eyre::Report) {
+        let entry = self.get_entry(wallet_pubkey);
+        entry.outcome = Outcome::Failed;
+        entry.error = Some(format!("{e:#}"));
+        entry.amounts += amounts;
+    }
+
+    /// Prints the summary and saves it as JSON and Markdown into the reports directory.
+    pub fn finish(mut self) -> eyre::Result<()> {
+        self.finished_at = get_timestamp();
+        self.processed = self.accounts.len();
+        self.succeeded = self.count(Outcome::Succeeded);
+        self.skipped = self.count(Outcome::Skipped);
+        self.failed = self.count(Outcome::Failed);
+
+        for account in &self.accounts {
+            self.totals += account.amounts;
+        }
+
+        let totals = &self.totals;
+
+        tracing::info!(
+            "Run summary ({}): processed {} | succeeded {} | skipped {} | failed {}",
+            self.operation,
+            self.processed,
+            self.succeeded,
+            self.skipped,
+            self.failed
+        );
+        tracing::info!(
+            "GRASS claimed: {} | GRASS forwarded: {}",
+            to_grass(totals.grass_claimed),
+            to_grass(totals.grass_forwarded)
+        );
+        tracing::info!(
+            "SOL collected: {} | rent reclaimed: {} | fees paid: {} | close fees paid: {}",
+            lamports_to_sol(totals.sol_collected),
+            lamports_to_sol(totals.rent_reclaimed),
+            lamports_to_sol(totals.fees_paid),
+            lamports_to_sol(totals.close_fees_paid)
+        );
+
+        std::fs::create_dir_all(REPORTS_DIR_PATH)?;
+
+        let path = format!("{REPORTS_DIR_PATH}/{}-{}", self.operation, self.started_at);
+
+        std::fs::write(format!("{path}.json"), serde_json::to_string_pretty(&self)?)?;
+        std::fs::write(format!("{path}.md"), self.to_markdown()?)?;
+
+        tracing::info!("Report saved to `{path}.json` and `{path}.md`");
+
+        Ok(())
+    }
+
+    fn count(&self, outcome: Outcome) -> usize {
+        self.accounts
+            .iter()
+            .filter(|account| account.outcome == outcome)
+            .count()
+    }
+
+    fn to_markdown(&self) -> eyre::Result<String> {
+        let totals = &self.totals;
+        let mut md = String::new();
+
+        writeln!(md, "# Run report: {}\n", self.operation)?;
+        writeln!(
+            md,
+            "Started at {}, finished at {} (unix time).\n",
+            self.started_at, self.finished_at
+        )?;
+
+        writeln!(md, "| Processed | Succeeded | Skipped | Failed |")?;
+        writeln!(md, "|---|---|---|---|")?;
+        writeln!(
+            md,
+            "| {} | {} | {} | {} |\n",
+            self.processed, self.succeeded, self.skipped, self.failed
+        )?;
+
+        writeln!(md, "## Totals\n")?;
+        writeln!(md, "| GRASS claimed | GRASS forwarded | SOL collected | Rent reclaimed | Fees paid | Close fees paid |")?;
+        writeln!(md, "|---|---|---|---|---|---|")?;
+        writeln!(
+            md,
+            "| {} | {} | {} | {} | {} | {} |\n",
+            to_grass(totals.grass_claimed),
+            to_grass(totals.grass_forwarded),
+            lamports_to_sol(totals.sol_collected),
+            lamports_to_sol(totals.rent_reclaimed),
+            lamports_to_sol(totals.fees_paid),
+            lamports_to_sol(totals.close_fees_paid)
+        )?;
+
+        writeln!(md, "## Accounts\n")?;
+        writeln!(md, "| Address | Outcome | Attempts | GRASS claimed | GRASS forwarded | SOL collected | Error |")?;
+        writeln!(md, "|---|---|---|---|---|---|---|")?;
+
+        for account in &self.accounts {
+            writeln!(
+                md,
+                "| `{}` | {} | {} | {} | {} | {} | {} |",
+                account.address,
+                account.outcome.as_str(),
+                account.attempts,
+                to_grass(account.amounts.grass_claimed),
+                to_grass(account.amounts.grass_forwarded),
+                lamports_to_sol(account.amounts.sol_collected),
+                account
+                    .error
+                    .as_deref()
+                    .unwrap_or("")
+                    .replace('|', "\\|")
+                    .replace('\n', " ")
+            )?;
+        }
+
+        Ok(md)
+    }
+}
@@ -0,0 +1,55 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use solana_sdk::pubkey::Pubkey;
+
+use super::{
+    claimer::claim_grass, closer::close_accounts, collect_and_close::collect_and_close,
+    collector::collect_sol, disperser::disperse_sol, forwarder::forward_grass,
+    lifecycle::run_lifecycle, receipts::prefetch_receipts,
+};
+
+use crate::{
+    config::Config,
+    db::{
+        database::Database,
+        failures::{FailureLog, Step},
+        selection::Selection,
+    },
+};
+
+/// Runs every step again, limited to the wallets that failed it, with the current config.
+pub async fn retry_failed(config: &Config) -> eyre::Result<()> {
+    let mut steps: BTreeMap<Step, Vec<Pubkey>> = BTreeMap::new();
+
+    for failure in FailureLog::read_all().await? {
+        match Pubkey::from_str(&failure.address) {
+            Ok(pubkey) => steps.entry(failure.step).or_default().push(pubkey),
+            Err(e) => tracing::error!("Invalid failed wallet `{}`: {}", failure.address, e),
+        }
+    }
+
+    if steps.is_empty() {
+        tracing::info!("No failed wallets to retry");
+        return Ok(());
+    }
+
+    for (step, wallets) in steps {
+        tracing::info!("Retrying `{step}` for {} failed wallets", wallets.len());
+
+        let db = Database::read().await;
+        let selection = Selection::from_addresses(&format!("failed {step}"), wallets);
+
+        match step {
+            Step::Receipt => prefetch_receipts(db, &selection, config).await?,
+            Step::Disperse => disperse_sol(db, &selection, config).await?,
+            Step::Claim => claim_grass(db, &selection, config).await?,
+            Step::Forward => forward_grass(db, &selection, config).await?,
+            Step::Close => close_accounts(db, &selection, config).await?,
+            Step::Collect => collect_sol(db, &selection, config).await?,
+            Step::CollectAndClose => collect_and_close(db, &selection, config).await?,
+            Step::Lifecycle => run_lifecycle(db, &selection, config).await?,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use super::overview::{get_versions, get_wallet_states, WalletState};
+
+use crate::{
+    config::Config,
+    db::{account::Account, database::Database, selection::Selection},
+    onchain::derive::{derive_claim_status, derive_merkle_distributor},
+};
+
+// the collector leaves at most the fee of its own transfer on a wallet
+const COLLECTED_SOL_THRESHOLD: u64 = 5000;
+
+fn report_change(changes: &mut Vec<String>, account: &Account, flag: &str, from: bool, to: bool) {
+    changes.push(format!(
+        "`{}` {}: {} -> {}",
+        account.get_pubkey(),
+        flag,
+        from,
+        to
+    ));
+}
+
+async fn sync_account(
+    provider: &RpcClient,
+    account: &mut Account,
+    state: &WalletState,
+    versions: &[u32],
+    claim_version: u32,
+    changes: &mut Vec<String>,
+) -> eyre::Result<()> {
+    let wallet_pubkey = account.get_pubkey();
+
+    for &version in versions {
+        let was_claimed = account.get_claimed(version);
+
+        match state.claim_statuses.get(&version) {
+            Some(&(allocation, sent_allocation)) => {
+                if account
+                    .get_claim(version)
+                    .map_or(0, |claim| claim.allocation)
+                    == 0
+                {
+                    account.set_allocation(version, allocation);
+                }
+
+                account.set_sent_allocation(version, sent_allocation);
+
+                let claimed = sent_allocation >= allocation;
+
+                if claimed && !was_claimed {
+                    let (merkle_distributor_pubkey, _) = derive_merkle_distributor(version);
+                    let (claim_status_pubkey, _) =
+                        derive_claim_status(&wallet_pubkey, &merkle_distributor_pubkey);
+
+                    let signature = provider
+                        .get_signatures_for_address(&claim_status_pubkey)
+                        .await?
+                        .into_iter()
+                        .find(|status| status.err.is_none())
+                        .map(|status| status.signature);
+
+                    account.set_claimed(version, signature);
+                } else if !claimed && was_claimed {
+                    account.reset_claimed(version);
+                }
+
+                if claimed != was_claimed {
+                    report_change(
+                        changes,
+                        account,
+                        &format!("claimed v{version}"),
+                        was_claimed,
+                        claimed,
+                    );
+                }
+            }
+            None => {
+                // wallets without an allocation are marked as claimed to be skipped
+                let has_allocation = account
+                    .get_claim(version)
+                    .is_some_and(|claim| claim.allocation != 0);
+
+                if was_claimed && has_allocation {
+                    account.reset_claimed(version);
+                    report_change(
+                        changes,
+                        account,
+                        &format!("claimed v{version}"),
+                        true,
+                        false,
+                    );
+                }
+            }
+        }
+    }
+
+    // an empty wallet says nothing about the later steps until the targeted version is claimed,
+    // so before that the flags are only cleared
+    let claimed = account.get_claimed(claim_version);
+
+    let closed_ata = !state.token_ata_exist && (claimed || account.get_closed_ata());
+
+    if account.get_closed_ata() != closed_ata {
+        report_change(
+            changes,
+            account,
+            "closed_ata",
+            account.get_closed_ata(),
+            closed_ata,
+        );
+        account.set_closed_ata(closed_ata);
+    }
+
+    let forwarded_grass = state.grass_balance == 0 && (claimed || account.get_forwarded_grass());
+
+    if account.get_forwarded_grass() != forwarded_grass {
+        report_change(
+            changes,
+            account,
+            "forwarded_grass",
+            account.get_forwarded_grass(),
+            forwarded_grass,
+        );
+        account.set_forwarded_grass(forwarded_grass);
+    }
+
+    let collected_sol =
+        state.lamports <= COLLECTED_SOL_THRESHOLD && (claimed || account.get_collected_sol());
+
+    if account.get_collected_sol() != collected_sol {
+        report_change(
+            changes,
+            account,
+            "collected_sol",
+            account.get_collected_sol(),
+            collected_sol,
+        );
+        account.set_collected_sol(collected_sol);
+    }
+
+    Ok(())
+}
+
+pub async fn sync_database(
+    mut db: Database,
+    selection: &Selection,
+    config: &Config,
+) -> eyre::Result<()> {
+    let selected = selection.select(&db, config);
+
+    let provider = RpcClient::new_with_timeout_and_commitment(
+        config.solana_rpc_url.clone(),
+        Duration::from_secs(60),
+        CommitmentConfig::processed(),
+    );
+
+    let versions = get_versions(&db.0, config);
+    let states = get_wallet_states(&provider, &db.0, &versions).await?;
+    let versions = versions.into_iter().collect::<Vec<_>>();
+
+    let mut changes = vec![];
+
+    for (account, state) in db.0.iter_mut().zip(&states) {
+        if !selected.contains(account) {
+            continue;
+        }
+
+        if let Err(e) = sync_account(
+            &provider,
+            account,
+            state,
+            &versions,
+            config.claim_version,
+            &mut changes,
+        )
+        .await
+        {
+            tracing::error!("Failed to sync `{}`: {}", account.get_pubkey(), e);
+        }
+    }
+
+    db.update();
+
+    for change in &changes {
+        tracing::info!("{}", change);
+    }
+
+    tracing::info!(
+        "Database synced with the chain, {} flags changed",
+        changes.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+// `getMultipleAccounts` accepts up to 100 addresses per request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+pub async fn get_multiple_accounts_chunked(
+    provider: &RpcClient,
+    pubkeys: &[Pubkey],
+) -> eyre::Result<HashMap<Pubkey, Account>> {
+    let mut accounts = HashMap::with_capacity(pubkeys.len());
+
+    for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let chunk_accounts = provider.get_multiple_accounts(chunk).await?;
+
+        for (pubkey, account) in chunk.iter().zip(chunk_accounts) {
+            if let Some(account) = account {
+                accounts.insert(*pubkey, account);
+            }
+        }
+    }
+
+    Ok(accounts)
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::Account, instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+
+use crate::config::Config;
+
+use super::accounts::get_multiple_accounts_chunked;
+
+/// Read-through cache of on-chain accounts. Batches are loaded up front with
+/// `getMultipleAccounts`, everything else falls back to single RPC calls. Accounts fetched
+/// longer than the configured TTL ago are read from the chain again.
+pub struct AccountCache<'a> {
+    provider: &'a RpcClient,
+    ttl: Duration,
+    accounts: HashMap<Pubkey, (Instant, Option<Account>)>,
+    rents: HashMap<usize, u64>,
+}
+
+impl<'a> AccountCache<'a> {
+    pub fn new(provider: &'a RpcClient, config: &Config) -> Self {
+        Self {
+            provider,
+            ttl: Duration::from_secs(config.account_cache_ttl_secs),
+            accounts: HashMap::new(),
+            rents: HashMap::new(),
+        }
+    }
+
+    /// Switches the client used for the following reads, e.g. to the proxied client of the
+    /// account being processed.
+    pub fn set_provider(&mut self, provider: &'a RpcClient) {
+        self.provider = provider;
+    }
+
+    pub async fn prefetch(&mut self, pubkeys: &[Pubkey]) -> eyre::Result<()> {
+        let mut fetched = get_multiple_accounts_chunked(self.provider, pubkeys).await?;
+        let fetched_at = Instant::now();
+
+        for pubkey in pubkeys {
+            self.accounts
+                .insert(*pubkey, (fetched_at, fetched.remove(pubkey)));
+        }
+
+        tracing::info!("Prefetched {} accounts", pubkeys.len());
+
+        Ok(())
+    }
+
+    pub async fn get_account(&mut self, pubkey: &Pubkey) -> eyre::Result<Option<Account>> {
+        if let Some((fetched_at, account)) = self.accounts.get(pubkey) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(account.clone());
+            }
+        }
+
+        let account = self
+            .provider
+            .get_account_with_commitment(pubkey, self.provider.commitment())
+            .await?
+            .value;
+
+        self.accounts
+            .insert(*pubkey, (Instant::now(), account.clone()));
+
+        Ok(account)
+    }
+
+    pub async fn exists(&mut self, pubkey: &Pubkey) -> eyre::Result<bool> {
+        Ok(self.get_account(pubkey).await?.is_some())
+    }
+
+    pub async fn get_balance(&mut self, pubkey: &Pubkey) -> eyre::Result<u64> {
+        Ok(self
+            .get_account(pubkey)
+            .await?
+            .map_or(0, |account| account.lamports))
+    }
+
+    /// Returns `None` when the token account doesn't exist.
+    pub async fn get_token_balance(&mut self, token_account: &Pubkey) -> eyre::Result<Option<u64>> {
+        match self.get_account(token_account).await? {
+            Some(account) => Ok(Some(
+                spl_token::state::Account::unpack(&account.data)?.amount,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn get_rent(&mut self, data_len: usize) -> eyre::Result<u64> {
+        if let Some(rent) = self.rents.get(&data_len) {
+            return Ok(*rent);
+        }
+
+        let rent = self
+            .provider
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await?;
+
+        self.rents.insert(data_len, rent);
+
+        Ok(rent)
+    }
+
+    /// Drops every account touched by the instructions, so the next read goes to the chain.
+    pub fn invalidate(&mut self, ixs: &[Instruction]) {
+        for ix in ixs {
+            for meta in &ix.accounts {
+                self.accounts.remove(&meta.pubkey);
+            }
+        }
+    }
+}
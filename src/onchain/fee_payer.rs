@@ -0,0 +1,117 @@
+use std::time::Instant;
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+};
+
+use crate::config::{Config, FeePayerRotation};
+
+struct FeePayer {
+    keypair: Keypair,
+    uses: usize,
+    last_used: Option<Instant>,
+}
+
+pub struct FeePayerPool {
+    payers: Vec<FeePayer>,
+    rotation: FeePayerRotation,
+    min_balance: u64,
+    cursor: usize,
+}
+
+impl FeePayerPool {
+    /// Returns an empty pool when external fee payment is disabled.
+    pub fn from_config(config: &Config) -> eyre::Result<Self> {
+        let payers = match config.use_external_fee_pay {
+            true => config
+                .external_fee_payer_pks
+                .iter()
+                .map(|pk| FeePayer {
+                    keypair: Keypair::from_base58_string(pk),
+                    uses: 0,
+                    last_used: None,
+                })
+                .collect(),
+            false => vec![],
+        };
+
+        if config.use_external_fee_pay && payers.is_empty() {
+            eyre::bail!("External fee payment is enabled, but no fee payers are configured");
+        }
+
+        Ok(Self {
+            payers,
+            rotation: config.fee_payer_rotation,
+            min_balance: sol_to_lamports(config.fee_payer_min_balance),
+            cursor: 0,
+        })
+    }
+
+    pub fn pubkeys(&self) -> Vec<Pubkey> {
+        self.payers
+            .iter()
+            .map(|payer| payer.keypair.pubkey())
+            .collect()
+    }
+
+    fn candidates(&self) -> Vec<usize> {
+        let mut indexes = (0..self.payers.len())
+            .map(|offset| (self.cursor + offset) % self.payers.len())
+            .collect::<Vec<_>>();
+
+        if self.rotation == FeePayerRotation::LeastUsed {
+            indexes.sort_by_key(|&index| (self.payers[index].uses, self.payers[index].last_used));
+        }
+
+        indexes
+    }
+
+    /// Picks the next fee payer holding at least the minimum balance. Returns `None` when
+    /// external fee payment is disabled and an error when no fee payer is above the threshold.
+    /// Payers whose balance can't be fetched are skipped for this pick.
+    pub async fn next(&mut self, provider: &RpcClient) -> eyre::Result<Option<Keypair>> {
+        if self.payers.is_empty() {
+            return Ok(None);
+        }
+
+        for index in self.candidates() {
+            let payer = &self.payers[index];
+            let balance = match provider.get_balance(&payer.keypair.pubkey()).await {
+                Ok(balance) => balance,
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to get the balance of fee payer `{}`, skipping it: {e}",
+                        payer.keypair.pubkey()
+                    );
+                    continue;
+                }
+            };
+
+            if balance < self.min_balance {
+                tracing::warn!(
+                    "Fee payer `{}` is below the minimum balance: {} | {}",
+                    payer.keypair.pubkey(),
+                    lamports_to_sol(balance),
+                    lamports_to_sol(self.min_balance)
+                );
+                continue;
+            }
+
+            let payer = &mut self.payers[index];
+            payer.uses += 1;
+            payer.last_used = Some(Instant::now());
+            self.cursor = (index + 1) % self.payers.len();
+
+            return Ok(Some(payer.keypair.insecure_clone()));
+        }
+
+        eyre::bail!(
+            "No fee payer with the minimum balance of {} SOL is available, stopping",
+            lamports_to_sol(self.min_balance)
+        )
+    }
+}
@@ -5,7 +5,7 @@ use solana_program::pubkey::Pubkey;
 use solana_sdk::native_token::{lamports_to_sol, sol_to_lamports};
 
 use super::constants::{
-    ASSOCIATED_TOKEN_PROGRAM_ID, CLOSE_PUBKEY, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID,
+    ASSOCIATED_TOKEN_PROGRAM_ID, CLOSE_PUBKEY, MEMO_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID,
 };
 use super::typedefs::CreateAtaArgs;
 use super::{
@@ -36,6 +36,11 @@ impl InstructionData for ClaimInput {
     const INSTRUCTION_NAME: &'static str = "claim";
 }
 
+/// Share of the ATA rent sent to the close fee address when the ATA is closed.
+pub fn get_close_fee(rent: u64) -> u64 {
+    sol_to_lamports(lamports_to_sol(rent) * 0.03)
+}
+
 pub struct Instructions {}
 
 impl Instructions {
@@ -83,13 +88,21 @@ impl Instructions {
         }
     }
 
+    pub fn memo(memo: &str, signer_pubkey: &Pubkey) -> Instruction {
+        Instruction {
+            program_id: MEMO_PROGRAM_ID,
+            accounts: vec![AccountMeta::new_readonly(*signer_pubkey, true)],
+            data: memo.as_bytes().to_vec(),
+        }
+    }
+
     pub fn close_account(
         wallet_token_ata: &Pubkey,
         wallet_pubkey: &Pubkey,
         payer_pubkey: &Pubkey,
         rent: u64,
     ) -> [Instruction; 2] {
-        let close_amount = sol_to_lamports(lamports_to_sol(rent) * 0.03);
+        let close_amount = get_close_fee(rent);
 
         [
             spl_token::instruction::close_account(
@@ -1,6 +1,10 @@
+pub mod accounts;
+pub mod cache;
 pub mod constants;
 pub mod derive;
+pub mod fee_payer;
 pub mod ixs;
 pub mod state;
+pub mod transport;
 pub mod tx;
 pub mod typedefs;
@@ -1,6 +1,8 @@
 use borsh::BorshDeserialize;
 use solana_sdk::pubkey::Pubkey;
 
+pub const CLAIM_STATUS_LEN: usize = 64;
+
 #[derive(BorshDeserialize)]
 pub struct ClaimStatus {
     _claimant: Pubkey,
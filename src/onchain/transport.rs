@@ -0,0 +1,144 @@
+use std::{collections::HashMap, time::Duration};
+
+use async_trait::async_trait;
+use solana_client::{
+    client_error::Result as ClientResult,
+    http_sender::HttpSender,
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_request::RpcRequest,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use crate::{config::Config, db::database::Database};
+
+const RPC_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Sends `sendTransaction` and every other RPC call through separate transports, so reads and
+/// sends can be proxied independently.
+struct SplitSender {
+    read: HttpSender,
+    send: HttpSender,
+}
+
+#[async_trait]
+impl RpcSender for SplitSender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        match request {
+            RpcRequest::SendTransaction => self.send.send(request, params).await,
+            _ => self.read.send(request, params).await,
+        }
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        let read = self.read.get_transport_stats();
+        let send = self.send.get_transport_stats();
+
+        RpcTransportStats {
+            request_count: read.request_count + send.request_count,
+            elapsed_time: read.elapsed_time + send.elapsed_time,
+            rate_limited_time: read.rate_limited_time + send.rate_limited_time,
+        }
+    }
+
+    fn url(&self) -> String {
+        self.read.url()
+    }
+}
+
+fn build_http_sender(url: &str, proxy: Option<&str>) -> eyre::Result<HttpSender> {
+    let Some(proxy) = proxy else {
+        return Ok(HttpSender::new_with_timeout(url, RPC_TIMEOUT));
+    };
+
+    let proxy = reqwest_rpc::Proxy::all(proxy)
+        .map_err(|e| eyre::eyre!("Invalid RPC proxy `{proxy}`: {e}"))?;
+
+    let client = reqwest_rpc::Client::builder()
+        .proxy(proxy)
+        .timeout(RPC_TIMEOUT)
+        .build()
+        .map_err(|e| eyre::eyre!("Failed to build a proxied RPC client: {e}"))?;
+
+    Ok(HttpSender::new_with_client(url, client))
+}
+
+/// RPC clients of the run: a direct one and, when enabled, one per proxy.
+pub struct RpcPool {
+    direct: RpcClient,
+    proxied: HashMap<String, RpcClient>,
+    proxy_reads: bool,
+}
+
+impl RpcPool {
+    /// Pool with a client for every account proxy of the database.
+    pub fn new(config: &Config, db: &Database) -> eyre::Result<Self> {
+        Self::with_proxies(config, db.0.iter().filter_map(|a| a.get_proxy()))
+    }
+
+    /// Pool with a client for every given proxy, e.g. the proxies of a `ProxyPool`.
+    pub fn with_proxies<'p>(
+        config: &Config,
+        proxies: impl Iterator<Item = &'p str>,
+    ) -> eyre::Result<Self> {
+        let direct = RpcClient::new_with_timeout_and_commitment(
+            config.solana_rpc_url.clone(),
+            RPC_TIMEOUT,
+            CommitmentConfig::processed(),
+        );
+
+        let mut proxied = HashMap::new();
+
+        if config.rpc_proxy_reads || config.rpc_proxy_sends {
+            for proxy in proxies {
+                if proxied.contains_key(proxy) {
+                    continue;
+                }
+
+                let sender = SplitSender {
+                    read: build_http_sender(
+                        &config.solana_rpc_url,
+                        config.rpc_proxy_reads.then_some(proxy),
+                    )?,
+                    send: build_http_sender(
+                        &config.solana_rpc_url,
+                        config.rpc_proxy_sends.then_some(proxy),
+                    )?,
+                };
+
+                let client = RpcClient::new_sender(
+                    sender,
+                    RpcClientConfig::with_commitment(CommitmentConfig::processed()),
+                );
+
+                proxied.insert(proxy.to_string(), client);
+            }
+        }
+
+        Ok(Self {
+            direct,
+            proxied,
+            proxy_reads: config.rpc_proxy_reads,
+        })
+    }
+
+    pub fn direct(&self) -> &RpcClient {
+        &self.direct
+    }
+
+    /// Client of the proxy, or the direct one when RPC proxying is disabled.
+    pub fn get(&self, proxy: Option<&str>) -> &RpcClient {
+        proxy
+            .and_then(|proxy| self.proxied.get(proxy))
+            .unwrap_or(&self.direct)
+    }
+
+    pub fn proxy_reads(&self) -> bool {
+        self.proxy_reads
+    }
+}
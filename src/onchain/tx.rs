@@ -1,17 +1,44 @@
+use std::fmt::{self, Display};
+
 use crate::utils::constants::SOLANA_EXPLORER_URL;
-use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{
+        RpcSendTransactionConfig, RpcSimulateTransactionAccountsConfig,
+        RpcSimulateTransactionConfig,
+    },
+};
 use solana_program::hash::Hash;
 use solana_sdk::{
+    account::Account,
     commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+    signature::Signature,
     transaction::Transaction,
 };
 use solana_transaction_status::UiTransactionEncoding;
 
+/// Transaction that was sent, but didn't confirm.
+#[derive(Debug)]
+pub struct TxFailed {
+    pub signature: Signature,
+    pub reason: String,
+}
+
+impl Display for TxFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Transaction failed: {}", self.reason)
+    }
+}
+
+impl std::error::Error for TxFailed {}
+
 pub async fn send_and_confirm_tx(
     provider: &RpcClient,
     tx: Transaction,
     recent_blockhash: &Hash,
-) -> eyre::Result<()> {
+) -> eyre::Result<Signature> {
     let tx_config = RpcSendTransactionConfig {
         skip_preflight: false,
         preflight_commitment: Some(CommitmentLevel::Confirmed),
@@ -34,17 +61,69 @@ pub async fn send_and_confirm_tx(
             {
                 Ok(_) => {
                     tracing::info!("Transaction confirmed");
+                    Ok(tx_signature)
                 }
 
-                Err(e) => {
-                    return Err(eyre::eyre!("Transaction failed: {}", e));
+                Err(e) => Err(TxFailed {
+                    signature: tx_signature,
+                    reason: e.to_string(),
                 }
+                .into()),
             }
         }
-        Err(e) => {
-            return Err(eyre::eyre!("Failed to send tx: {e}"));
-        }
+        Err(e) => Err(eyre::eyre!("Failed to send tx: {e}")),
+    }
+}
+
+/// Simulates a transaction without checking signatures, so instructions can be validated before
+/// the signers are available.
+pub async fn simulate_tx(provider: &RpcClient, tx: &Transaction) -> eyre::Result<()> {
+    simulate_tx_accounts(provider, tx, &[]).await.map(|_| ())
+}
+
+/// Simulates a transaction without checking signatures and returns the state of `addresses`
+/// after it, so the effect of instructions can be read before the signers are available.
+pub async fn simulate_tx_accounts(
+    provider: &RpcClient,
+    tx: &Transaction,
+    addresses: &[Pubkey],
+) -> eyre::Result<Vec<Option<Account>>> {
+    let sim_config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::processed()),
+        accounts: (!addresses.is_empty()).then(|| RpcSimulateTransactionAccountsConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            addresses: addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+        }),
+        ..Default::default()
+    };
+
+    let result = provider
+        .simulate_transaction_with_config(tx, sim_config)
+        .await?
+        .value;
+
+    if let Some(err) = result.err {
+        let logs = result.logs.unwrap_or_default();
+        let reason = logs
+            .iter()
+            .rev()
+            .find(|log| log.contains("Error"))
+            .or(logs.last())
+            .cloned()
+            .unwrap_or_default();
+
+        eyre::bail!("Simulation failed: {err} {reason}");
     }
 
-    Ok(())
+    Ok(result
+        .accounts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|account| account.and_then(|account| account.decode()))
+        .collect())
 }
@@ -1,63 +1,408 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant, SystemTime},
+};
 
-use reqwest::{header::HeaderMap, Client, Method};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    tls, Client, Method, StatusCode, Url,
+};
 use serde::{de::DeserializeOwned, Serialize};
 
+use crate::config::{Config, TlsVersion};
+
+use super::misc::random_in_range;
+
 #[derive(Clone)]
 pub struct RequestParams<'a, S: Serialize> {
     pub url: &'a str,
     pub method: Method,
     pub body: Option<S>,
     pub query_args: Option<HashMap<&'a str, &'a str>>,
-    pub proxy: Option<&'a reqwest::Proxy>,
+    pub proxy: Option<&'a str>,
     pub headers: Option<HeaderMap>,
 }
 
+/// Client, retry and rate limiting settings shared by every HTTP request.
+#[derive(Clone, Copy)]
+pub struct HttpPolicy {
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub http2_prior_knowledge: bool,
+    pub min_tls_version: tls::Version,
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub proxy_rps: f64,
+    pub host_rps: f64,
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(10),
+            http2_prior_knowledge: false,
+            min_tls_version: tls::Version::TLS_1_2,
+            attempts: 1,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            proxy_rps: 0.0,
+            host_rps: 0.0,
+        }
+    }
+}
+
+impl HttpPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        let min_tls_version = match config.http_min_tls_version {
+            TlsVersion::Tls12 => tls::Version::TLS_1_2,
+            TlsVersion::Tls13 => tls::Version::TLS_1_3,
+        };
+
+        Self {
+            timeout: Duration::from_secs(config.http_timeout_secs),
+            connect_timeout: Duration::from_secs(config.http_connect_timeout_secs),
+            http2_prior_knowledge: config.http2_prior_knowledge,
+            min_tls_version,
+            attempts: config.http_retry_attempts.max(1),
+            base_delay: Duration::from_millis(config.http_retry_base_delay_ms),
+            max_delay: Duration::from_millis(config.http_retry_max_delay_ms),
+            proxy_rps: config.http_proxy_rps,
+            host_rps: config.http_host_rps,
+        }
+    }
+
+    /// Exponential backoff with full jitter in the upper half of the window.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay)
+            .as_millis() as u64;
+
+        Duration::from_millis(random_in_range([delay / 2, delay]))
+    }
+
+    /// Delay before the next attempt: `Retry-After` when the server sent it, capped at
+    /// `max_delay`, otherwise the backoff of the attempt.
+    fn retry_delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        retry_after
+            .map(|retry_after| retry_after.min(self.max_delay))
+            .unwrap_or_else(|| self.backoff(attempt))
+    }
+}
+
+static HTTP_POLICY: OnceLock<HttpPolicy> = OnceLock::new();
+
+pub fn set_http_policy(policy: HttpPolicy) {
+    let _ = HTTP_POLICY.set(policy);
+}
+
+fn http_policy() -> HttpPolicy {
+    HTTP_POLICY.get().copied().unwrap_or_default()
+}
+
+static CLIENTS: OnceLock<Mutex<HashMap<Option<String>, Client>>> = OnceLock::new();
+
+/// Returns the shared client of the proxy, so connections are reused between requests.
+/// A proxy that can't be set up is an error, requests never fall back to a direct connection.
+fn get_client(proxy: Option<&str>, policy: &HttpPolicy) -> eyre::Result<Client> {
+    let mut clients = CLIENTS
+        .get_or_init(Default::default)
+        .lock()
+        .expect("Clients lock to be healthy");
+
+    let key = proxy.map(str::to_string);
+
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+
+    let mut builder = Client::builder()
+        .timeout(policy.timeout)
+        .connect_timeout(policy.connect_timeout)
+        .min_tls_version(policy.min_tls_version);
+
+    if policy.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    }
+
+    if let Some(proxy) = proxy {
+        let proxy =
+            reqwest::Proxy::all(proxy).map_err(|e| eyre::eyre!("Invalid proxy `{proxy}`: {e}"))?;
+        builder = builder.proxy(proxy);
+    }
+
+    let client = builder.build().map_err(|e| match proxy {
+        Some(proxy) => eyre::eyre!("Failed to build a client with proxy `{proxy}`: {e}"),
+        None => eyre::eyre!("Failed to build a client: {e}"),
+    })?;
+
+    clients.insert(key, client.clone());
+
+    Ok(client)
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+static BUCKETS: OnceLock<Mutex<HashMap<String, TokenBucket>>> = OnceLock::new();
+
+/// Waits for a token of the bucket behind `key`, refilled at `rps` tokens per second.
+/// A non-positive rate disables the limit.
+async fn acquire_token(key: &str, rps: f64) {
+    if rps <= 0.0 {
+        return;
+    }
+
+    let burst = rps.max(1.0);
+
+    loop {
+        let wait = {
+            let mut buckets = BUCKETS
+                .get_or_init(Default::default)
+                .lock()
+                .expect("Token buckets lock to be healthy");
+
+            let now = Instant::now();
+            let bucket = buckets.entry(key.to_string()).or_insert(TokenBucket {
+                tokens: burst,
+                updated_at: now,
+            });
+
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rps).min(burst);
+            bucket.updated_at = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return;
+            }
+
+            Duration::from_secs_f64((1.0 - bucket.tokens) / rps)
+        };
+
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Parses `Retry-After` given either in seconds or as an HTTP date.
+fn get_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
 pub async fn send_http_request<R: DeserializeOwned>(
     request_params: RequestParams<'_, impl Serialize>,
 ) -> eyre::Result<R> {
-    let client = request_params.proxy.map_or_else(Client::new, |proxy| {
-        Client::builder()
-            .proxy(proxy.clone())
-            .build()
-            .unwrap_or_else(|err| {
-                tracing::error!("Failed to build a client with proxy: {proxy:?}. Error: {err}");
-                Client::new()
-            })
-    });
+    let policy = http_policy();
+
+    let client = get_client(request_params.proxy, &policy)?;
+
+    let host = Url::parse(request_params.url)?
+        .host_str()
+        .unwrap_or_default()
+        .to_string();
+
+    let mut attempt = 0;
+
+    loop {
+        attempt += 1;
+
+        acquire_token(&format!("host:{host}"), policy.host_rps).await;
+
+        if let Some(proxy) = request_params.proxy {
+            acquire_token(&format!("proxy:{proxy}"), policy.proxy_rps).await;
+        }
+
+        let mut request = client.request(request_params.method.clone(), request_params.url);
+
+        if let Some(params) = &request_params.query_args {
+            request = request.query(&params);
+        }
+
+        if let Some(body) = &request_params.body {
+            request = request.json(&body);
+        }
+
+        if let Some(headers) = request_params.headers.as_ref() {
+            request = request.headers(headers.clone());
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) if attempt < policy.attempts => {
+                let delay = policy.backoff(attempt - 1);
+                tracing::warn!(
+                    "Request failed: {}, retrying in {:?} ({}/{})",
+                    e,
+                    delay,
+                    attempt,
+                    policy.attempts
+                );
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+            Err(e) => {
+                tracing::error!("Request failed: {}", e);
+                return Err(e.into());
+            }
+        };
+
+        let status = response.status();
+        let retry_after = get_retry_after(response.headers());
+
+        let text = response
+            .text()
+            .await
+            .inspect_err(|e| tracing::error!("Failed to retrieve response text: {}", e))?;
+
+        if is_retryable(status) && attempt < policy.attempts {
+            let delay = policy.retry_delay(attempt - 1, retry_after);
+            tracing::warn!(
+                "Status code {}, retrying in {:?} ({}/{})",
+                status,
+                delay,
+                attempt,
+                policy.attempts
+            );
+            tokio::time::sleep(delay).await;
+            continue;
+        }
 
-    let mut request = client.request(request_params.method.clone(), request_params.url);
+        if !status.is_success() {
+            eyre::bail!("Status code not 200: {status}, {text}")
+        }
 
-    if let Some(params) = &request_params.query_args {
-        request = request.query(&params);
+        let deserialized_body = serde_json::from_str::<R>(&text).inspect_err(|e| {
+            tracing::error!("Failed to deserialize response: {}\n {} ", e, text)
+        })?;
+
+        return Ok(deserialized_body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(base_delay_ms: u64, max_delay_ms: u64) -> HttpPolicy {
+        HttpPolicy {
+            base_delay: Duration::from_millis(base_delay_ms),
+            max_delay: Duration::from_millis(max_delay_ms),
+            ..Default::default()
+        }
+    }
+
+    fn retry_after_headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, value.parse().unwrap());
+        headers
     }
 
-    if let Some(body) = &request_params.body {
-        request = request.json(&body);
+    #[test]
+    fn retry_after_in_seconds() {
+        assert_eq!(
+            get_retry_after(&retry_after_headers(" 120 ")),
+            Some(Duration::from_secs(120))
+        );
     }
 
-    if let Some(headers) = request_params.headers.as_ref() {
-        request = request.headers(headers.clone());
+    #[test]
+    fn retry_after_as_http_date() {
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        let delay = get_retry_after(&retry_after_headers(&date)).unwrap();
+
+        assert!(delay > Duration::from_secs(58) && delay <= Duration::from_secs(60));
     }
 
-    let response = request
-        .send()
-        .await
-        .inspect_err(|e| tracing::error!("Request failed: {}", e))?;
+    #[test]
+    fn retry_after_in_the_past_is_immediate() {
+        let date = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(60));
+
+        assert_eq!(
+            get_retry_after(&retry_after_headers(&date)),
+            Some(Duration::ZERO)
+        );
+    }
 
-    let status = response.status();
+    #[test]
+    fn retry_after_missing_or_malformed() {
+        assert_eq!(get_retry_after(&HeaderMap::new()), None);
+        assert_eq!(get_retry_after(&retry_after_headers("soon")), None);
+        assert_eq!(get_retry_after(&retry_after_headers("-5")), None);
+    }
 
-    let text = response
-        .text()
-        .await
-        .inspect_err(|e| tracing::error!("Failed to retrieve response text: {}", e))?;
+    #[test]
+    fn retry_after_is_capped_at_max_delay() {
+        let policy = policy(500, 30_000);
 
-    if !status.is_success() {
-        eyre::bail!("Status code not 200: {status}, {text}")
+        assert_eq!(
+            policy.retry_delay(0, Some(Duration::from_secs(120))),
+            Duration::from_secs(30)
+        );
+        assert_eq!(
+            policy.retry_delay(0, Some(Duration::from_secs(5))),
+            Duration::from_secs(5)
+        );
     }
 
-    let deserialized_body = serde_json::from_str::<R>(&text)
-        .inspect_err(|e| tracing::error!("Failed to deserialize response: {}\n {} ", e, text))?;
+    #[test]
+    fn backoff_doubles_within_jitter_window_up_to_max_delay() {
+        let policy = policy(100, 10_000);
+
+        for (attempt, delay_ms) in [(0, 100), (1, 200), (3, 800), (6, 6_400), (7, 10_000)] {
+            let delay = policy.backoff(attempt);
+            assert!(
+                delay >= Duration::from_millis(delay_ms / 2)
+                    && delay <= Duration::from_millis(delay_ms),
+                "attempt {attempt}: {delay:?}"
+            );
+        }
 
-    Ok(deserialized_body)
+        assert!(policy.backoff(u32::MAX) <= Duration::from_secs(10));
+        assert!(policy.retry_delay(7, None) <= Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn acquire_token_without_rate_returns_immediately() {
+        let started_at = Instant::now();
+
+        for _ in 0..100 {
+            acquire_token("test:unlimited", 0.0).await;
+        }
+
+        assert!(started_at.elapsed() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn acquire_token_waits_once_burst_is_spent() {
+        let started_at = Instant::now();
+
+        for _ in 0..10 {
+            acquire_token("test:limited", 10.0).await;
+        }
+
+        assert!(started_at.elapsed() < Duration::from_millis(50));
+
+        acquire_token("test:limited", 10.0).await;
+        acquire_token("test:limited", 10.0).await;
+
+        assert!(started_at.elapsed() >= Duration::from_millis(150));
+    }
 }
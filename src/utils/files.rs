@@ -16,3 +16,24 @@ pub async fn read_file_lines(path: impl AsRef<Path>) -> eyre::Result<Vec<String>
 
     Ok(contents)
 }
+
+/// Same as `read_file_lines`, but keeps the 1-based line number of every non-empty line.
+pub async fn read_numbered_file_lines(
+    path: impl AsRef<Path>,
+) -> eyre::Result<Vec<(usize, String)>> {
+    let file = tokio::fs::read(path).await?;
+    let mut lines = file.lines();
+
+    let mut contents = vec![];
+    let mut line_number = 0;
+    while let Some(line) = lines.next_line().await? {
+        line_number += 1;
+
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            contents.push((line_number, trimmed.to_string()));
+        }
+    }
+
+    Ok(contents)
+}
@@ -3,3 +3,5 @@ pub mod fetch;
 pub mod files;
 pub mod logger;
 pub mod misc;
+pub mod proxy;
+pub mod proxy_pool;